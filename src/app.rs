@@ -1,76 +1,400 @@
-
 use sdl2::event::Event;
 use sdl2::event::WindowEvent;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 
 use game::{Game, Direction};
+use graphics;
+use menu::{Menu, MenuInput};
+use mode::Mode;
+use config::{Settings, ArenaSize};
+use input::{self, Action, Bindings, ControlScheme, KeyPreset};
+use speed::SpeedCurve;
+use pointer::{self, Gesture, Pointer};
+use theme::Theme;
 use constants::*;
-use TimeUpdate;
+use {TimeUpdate, EventIterator};
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum AppState {
     Start,
+    ModeSelect,
+    Options,
+    HighScores,
+    Controls,
+    /// The keys that can't be rebound, reached from the controls
+    FixedKeys,
+    Credits,
     Game,
+    Paused,
     GameOver,
+    /// Where the points of the last game came from
+    ScoreBreakdown,
     Quit
 }
 
-pub fn start(events: &mut Iterator<Item=Event>, time_update: TimeUpdate, game: &mut Game) -> (AppState, bool) {
-    for event in events {
-        match event {
-            Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
-                return (AppState::Quit, false);
+impl AppState {
+    /// Screens that are menus or other text screens reached from the start menu.
+    pub fn is_menu(self) -> bool {
+        !matches!(self, AppState::Game | AppState::Paused | AppState::GameOver | AppState::Quit)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MainItem {
+    NewGame,
+    ModeSelect,
+    Options,
+    HighScores,
+    Controls,
+    Credits,
+    Quit,
+}
+
+impl MainItem {
+    pub fn all() -> Vec<MainItem> {
+        use self::MainItem::*;
+        vec![NewGame, ModeSelect, Options, HighScores, Controls, Credits, Quit]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MainItem::NewGame => "NEW GAME",
+            MainItem::ModeSelect => "MODE",
+            MainItem::Options => "OPTIONS",
+            MainItem::HighScores => "HIGH SCORES",
+            MainItem::Controls => "CONTROLS",
+            MainItem::Credits => "CREDITS",
+            MainItem::Quit => "QUIT",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptionItem {
+    SpeedCurve,
+    ArenaSize,
+    Wrap,
+    Apples,
+    Theme,
+    Volume,
+    Fullscreen,
+    ControlScheme,
+    Keys,
+    Back,
+}
+
+impl OptionItem {
+    pub fn all() -> Vec<OptionItem> {
+        use self::OptionItem::*;
+        vec![SpeedCurve, ArenaSize, Wrap, Apples, Theme, Volume, Fullscreen, ControlScheme, Keys, Back]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ControlItem {
+    Bind(Action),
+    Preset(KeyPreset),
+    FixedKeys,
+    Back,
+}
+
+impl ControlItem {
+    pub fn all() -> Vec<ControlItem> {
+        let mut items: Vec<_> = Action::all().into_iter().map(ControlItem::Bind).collect();
+        items.extend(KeyPreset::all().into_iter().map(ControlItem::Preset));
+        items.push(ControlItem::FixedKeys);
+        items.push(ControlItem::Back);
+        items
+    }
+}
+
+/// The menus, kept around so that each remembers its selection.
+pub struct Menus {
+    pub main: Menu<MainItem>,
+    pub mode: Menu<Mode>,
+    pub options: Menu<OptionItem>,
+    pub controls: Menu<ControlItem>,
+    /// The action waiting for a key on the controls screen
+    pub rebinding: Option<Action>,
+    /// Where the controls screen goes back to
+    pub controls_parent: AppState,
+}
+
+impl Menus {
+    pub fn new() -> Menus {
+        Menus {
+            main: Menu::new(MainItem::all()),
+            mode: Menu::new(Mode::all()),
+            options: Menu::new(OptionItem::all()),
+            controls: Menu::new(ControlItem::all()),
+            rebinding: None,
+            controls_parent: AppState::Start,
+        }
+    }
+}
+
+/// What a key press or a click did to a menu. Mouse events are in logical
+/// coordinates, so they can be matched against where the items are drawn.
+fn menu_input<T: Copy>(event: &Event, menu: &mut Menu<T>, bindings: &Bindings) -> Option<MenuInput<T>> {
+    if let Event::MouseButtonDown { mouse_btn: MouseButton::Left, y, .. } = *event {
+        return graphics::menu_item_at(y, menu.items().len(), menu.selected_index()).map(|index| menu.click(index));
+    }
+    input::menu_action(event, bindings).and_then(|action| menu.handle(action))
+}
+
+pub fn start(events: &mut EventIterator, time_update: TimeUpdate, menus: &mut Menus, game: &mut Game, bindings: &Bindings) -> (AppState, bool) {
+    let mut redraw_needed = false;
+    while let Some(event) = events.next() {
+        match menu_input(&event, &mut menus.main, bindings) {
+            Some(MenuInput::Moved) => {
+                events.menu_moved = true;
+                redraw_needed = true;
             },
-            Event::KeyDown {..} => {
-                game.reset();
-                return (AppState::Game, true);
+            Some(MenuInput::Chosen(item)) => {
+                let state = match item {
+                    MainItem::NewGame => {
+                        game.reset();
+                        AppState::Game
+                    },
+                    MainItem::ModeSelect => AppState::ModeSelect,
+                    MainItem::Options => AppState::Options,
+                    MainItem::HighScores => AppState::HighScores,
+                    MainItem::Controls => {
+                        menus.controls_parent = AppState::Start;
+                        AppState::Controls
+                    },
+                    MainItem::Credits => AppState::Credits,
+                    MainItem::Quit => AppState::Quit,
+                };
+                return (state, true);
+            },
+            Some(MenuInput::Back) => {
+                return (AppState::Quit, false);
             },
             _ => {}
         }
     }
     let previous = (time_update.absolute - time_update.diff) / START_SCREEN_SPEED;
     let current = time_update.absolute / START_SCREEN_SPEED;
-    (AppState::Start, previous != current)
+    (AppState::Start, redraw_needed || previous != current)
 }
 
-pub fn game(events: &mut Iterator<Item=Event>, time_update: TimeUpdate, game: &mut Game) -> (AppState, bool) {
+/// Choosing a mode picks it for the next games and goes back to the start menu.
+pub fn mode_select(events: &mut EventIterator, menu: &mut Menu<Mode>, mode: &mut Mode, bindings: &Bindings) -> (AppState, bool) {
     let mut redraw_needed = false;
+    while let Some(event) = events.next() {
+        match menu_input(&event, menu, bindings) {
+            Some(MenuInput::Moved) => {
+                events.menu_moved = true;
+                redraw_needed = true;
+            },
+            Some(MenuInput::Chosen(chosen)) => {
+                *mode = chosen;
+                return (AppState::Start, true);
+            },
+            Some(MenuInput::Back) => {
+                return (AppState::Start, true);
+            },
+            _ => {}
+        }
+    }
+    (AppState::ModeSelect, redraw_needed)
+}
+
+/// Left and right step through the values of a setting, Enter goes forward.
+/// The changes go straight into `settings`; applying and saving them is up to the caller.
+pub fn options(events: &mut EventIterator, menus: &mut Menus, settings: &mut Settings, themes: &[Theme]) -> (AppState, bool) {
+    let mut redraw_needed = false;
+    while let Some(event) = events.next() {
+        let (item, delta) = match menu_input(&event, &mut menus.options, &settings.bindings) {
+            Some(MenuInput::Moved) => {
+                events.menu_moved = true;
+                redraw_needed = true;
+                continue;
+            },
+            Some(MenuInput::Back) | Some(MenuInput::Chosen(OptionItem::Back)) => {
+                return (AppState::Start, true);
+            },
+            Some(MenuInput::Chosen(OptionItem::Keys)) => {
+                menus.controls_parent = AppState::Options;
+                return (AppState::Controls, true);
+            },
+            Some(MenuInput::Chosen(item)) => (item, 1),
+            Some(MenuInput::Adjusted(item, delta)) => (item, delta),
+            None => continue,
+        };
+        adjust_setting(settings, item, delta, themes);
+        events.menu_moved = true;
+        redraw_needed = true;
+    }
+    (AppState::Options, redraw_needed)
+}
+
+/// The item `delta` steps away from `current`, wrapping around.
+fn step<T: Copy + PartialEq>(all: &[T], current: T, delta: i32) -> T {
+    let index = all.iter().position(|&item| item == current).unwrap_or(0) as i32;
+    all[(index + delta).rem_euclid(all.len() as i32) as usize]
+}
+
+fn adjust_setting(settings: &mut Settings, item: OptionItem, delta: i32, themes: &[Theme]) {
+    match item {
+        OptionItem::SpeedCurve => settings.speed_curve = step(&SpeedCurve::all(), settings.speed_curve, delta),
+        OptionItem::ArenaSize => settings.arena_size = step(&ArenaSize::all(), settings.arena_size, delta),
+        OptionItem::Wrap => settings.wrap = !settings.wrap,
+        OptionItem::Apples => settings.apple_count = (settings.apple_count as i32 - 1 + delta).rem_euclid(MAX_APPLES as i32) as u32 + 1,
+        OptionItem::Theme => {
+            let names: Vec<_> = themes.iter().map(|theme| &theme.name[..]).collect();
+            settings.theme = step(&names, &settings.theme[..], delta).to_string();
+        },
+        OptionItem::Volume => settings.volume = (settings.volume as i32 + delta).max(0).min(MAX_VOLUME as i32) as u32,
+        OptionItem::Fullscreen => settings.fullscreen = !settings.fullscreen,
+        OptionItem::ControlScheme => settings.control_scheme = step(&ControlScheme::all(), settings.control_scheme, delta),
+        OptionItem::Keys | OptionItem::Back => {},
+    }
+}
+
+/// Choosing an action waits for the next key press and binds it to the
+/// action; Escape cancels the wait.
+pub fn controls(events: &mut EventIterator, menus: &mut Menus, bindings: &mut Bindings) -> (AppState, bool) {
+    let mut redraw_needed = false;
+    while let Some(event) = events.next() {
+        if let Some(action) = menus.rebinding {
+            match event {
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {},
+                Event::KeyDown {keycode: Some(keycode), ..} => bindings.bind(action, keycode),
+                _ => continue,
+            }
+            menus.rebinding = None;
+            redraw_needed = true;
+            continue;
+        }
+        match menu_input(&event, &mut menus.controls, bindings) {
+            Some(MenuInput::Moved) => {
+                events.menu_moved = true;
+                redraw_needed = true;
+            },
+            Some(MenuInput::Chosen(ControlItem::Bind(action))) => {
+                menus.rebinding = Some(action);
+                redraw_needed = true;
+            },
+            Some(MenuInput::Chosen(ControlItem::Preset(preset))) => {
+                *bindings = Bindings::preset(preset);
+                events.menu_moved = true;
+                redraw_needed = true;
+            },
+            Some(MenuInput::Chosen(ControlItem::FixedKeys)) => {
+                return (AppState::FixedKeys, true);
+            },
+            Some(MenuInput::Chosen(ControlItem::Back)) | Some(MenuInput::Back) => {
+                return (menus.controls_parent, true);
+            },
+            _ => {}
+        }
+    }
+    (AppState::Controls, redraw_needed)
+}
+
+/// High scores, credits, the fixed keys and the score breakdown: text to
+/// read, then any key or a click to go back to `back`.
+pub fn info_screen(events: &mut EventIterator, state: AppState, back: AppState) -> (AppState, bool) {
     for event in events {
-        let mut direction = None;
         match event {
-            Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+            Event::KeyDown {..} | Event::MouseButtonDown {..} => return (back, true),
+            _ => {}
+        }
+    }
+    (state, false)
+}
+
+/// With the relative control scheme left and right turn the worm and up and down do nothing.
+/// Tapping the arena turns towards the tapped side of the worm's head and
+/// swiping turns the way of the swipe, whatever the control scheme.
+pub fn game(events: &mut Iterator<Item=Event>, time_update: TimeUpdate, game: &mut Game, bindings: &Bindings, scheme: ControlScheme, pointer: &mut Pointer) -> (AppState, bool) {
+    let mut redraw_needed = false;
+    for event in events {
+        if let Event::Window {win_event: WindowEvent::Exposed, ..} = event {
+            redraw_needed = true;
+        }
+        match pointer.handle(&event) {
+            Some(Gesture::Tap(x, y)) => {
+                let head = graphics::worm_head_center(game);
+                game.update_direction(pointer::direction(x - head.0, y - head.1));
+            },
+            Some(Gesture::Swipe(direction)) => game.update_direction(direction),
+            None => {},
+        }
+        let action = match input::action(&event, bindings) {
+            Some(action) => action,
+            None => continue,
+        };
+        match (action, scheme) {
+            (Action::Back, _) => {
                 game.reset();
                 return (AppState::Start, true);
             },
-            Event::KeyDown {keycode: Some(Keycode::Up), ..} => { direction = Some(Direction::Up); },
-            Event::KeyDown {keycode: Some(Keycode::Down), ..} => { direction = Some(Direction::Down); },
-            Event::KeyDown {keycode: Some(Keycode::Right), ..} => { direction = Some(Direction::Right); },
-            Event::KeyDown {keycode: Some(Keycode::Left), ..} => { direction = Some(Direction::Left); },
-            Event::Window {win_event: WindowEvent::Exposed, ..} => { redraw_needed = true; },
+            (Action::Pause, _) => {
+                return (AppState::Paused, true);
+            },
+            (Action::TurnRelativeLeft, _) | (Action::TurnLeft, ControlScheme::Relative) => game.turn_left(),
+            (Action::TurnRelativeRight, _) | (Action::TurnRight, ControlScheme::Relative) => game.turn_right(),
+            (_, ControlScheme::Relative) => {},
+            (Action::TurnUp, _) => game.update_direction(Direction::Up),
+            (Action::TurnDown, _) => game.update_direction(Direction::Down),
+            (Action::TurnLeft, _) => game.update_direction(Direction::Left),
+            (Action::TurnRight, _) => game.update_direction(Direction::Right),
             _ => {}
         }
-        if let Some(direction) = direction {
-            game.update_direction(direction);
-        }
     }
-    if !game.alive {
+    if game.is_over() {
         return (AppState::GameOver, true);
     }
     redraw_needed = game.tick(time_update.diff) || redraw_needed;
     (AppState::Game, redraw_needed)
 }
 
-pub fn game_over(events: &mut Iterator<Item=Event>, time_update: TimeUpdate) -> (AppState, bool) {
+/// The game stands still until Pause, Confirm or a click; Back gives up the game.
+pub fn paused(events: &mut Iterator<Item=Event>, game: &mut Game, bindings: &Bindings) -> (AppState, bool) {
+    let mut redraw_needed = false;
     for event in events {
-        match event {
-            Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+        if let Event::MouseButtonDown {..} = event {
+            return (AppState::Game, true);
+        }
+        match input::menu_action(&event, bindings) {
+            Some(Action::Pause) | Some(Action::Confirm) => {
+                return (AppState::Game, true);
+            },
+            Some(Action::Back) => {
+                game.reset();
+                return (AppState::Start, true);
+            },
+            _ => {}
+        }
+        if let Event::Window {win_event: WindowEvent::Exposed, ..} = event {
+            redraw_needed = true;
+        }
+    }
+    (AppState::Paused, redraw_needed)
+}
+
+/// Confirm or waiting goes on to the score breakdown; Back skips it.
+pub fn game_over(events: &mut Iterator<Item=Event>, time_update: TimeUpdate, bindings: &Bindings) -> (AppState, bool) {
+    for event in events {
+        if let Event::MouseButtonDown {..} = event {
+            return (AppState::ScoreBreakdown, true);
+        }
+        match input::menu_action(&event, bindings) {
+            Some(Action::Confirm) => {
+                return (AppState::ScoreBreakdown, true);
+            },
+            Some(Action::Back) => {
                 return (AppState::Start, false);
             },
             _ => {}
         }
     }
     if time_update.absolute > GAME_OVER_SCREEN_DURATION {
-        return (AppState::Start, false);
+        return (AppState::ScoreBreakdown, true);
     }
     let previous = (time_update.absolute - time_update.diff) / GAME_OVER_SCREEN_SPEED;
     let current = time_update.absolute / GAME_OVER_SCREEN_SPEED;
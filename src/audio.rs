@@ -0,0 +1,355 @@
+#![allow(dead_code)]
+
+//! Chiptune style sound effects and music, synthesized on the fly in SDL's
+//! audio callback. There are no sound files; everything is made of a few
+//! simple waveforms.
+
+use sdl2::Sdl;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use game::GameEvent;
+
+const SAMPLE_RATE: i32 = 44100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sound {
+    Eat,
+    Turn,
+    Death,
+    LevelClear,
+    MenuBlip,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Wave {
+    /// Square wave with the given duty cycle in eighths
+    Pulse(u8),
+    Triangle,
+    Noise,
+}
+
+/// One note of a sound effect: the pitch slides from `from` to `to` Hz over
+/// `length` milliseconds while the volume fades out linearly.
+#[derive(Clone, Copy)]
+struct Note {
+    wave: Wave,
+    from: f32,
+    to: f32,
+    length: u32,
+    volume: f32,
+}
+
+const fn note(wave: Wave, from: f32, to: f32, length: u32, volume: f32) -> Note {
+    Note { wave, from, to, length, volume }
+}
+
+const EAT: &[Note] = &[
+    note(Wave::Pulse(2), 660.0, 660.0, 40, 0.5),
+    note(Wave::Pulse(2), 990.0, 1320.0, 60, 0.5),
+];
+const TURN: &[Note] = &[
+    note(Wave::Pulse(4), 220.0, 180.0, 25, 0.2),
+];
+const DEATH: &[Note] = &[
+    note(Wave::Noise, 800.0, 100.0, 300, 0.6),
+    note(Wave::Triangle, 220.0, 55.0, 500, 0.7),
+];
+const LEVEL_CLEAR: &[Note] = &[
+    note(Wave::Pulse(4), 523.0, 523.0, 90, 0.4),
+    note(Wave::Pulse(4), 659.0, 659.0, 90, 0.4),
+    note(Wave::Pulse(4), 784.0, 784.0, 90, 0.4),
+    note(Wave::Pulse(4), 1047.0, 1047.0, 300, 0.4),
+];
+const MENU_BLIP: &[Note] = &[
+    note(Wave::Pulse(1), 1200.0, 1200.0, 30, 0.3),
+];
+
+fn notes(sound: Sound) -> &'static [Note] {
+    match sound {
+        Sound::Eat => EAT,
+        Sound::Turn => TURN,
+        Sound::Death => DEATH,
+        Sound::LevelClear => LEVEL_CLEAR,
+        Sound::MenuBlip => MENU_BLIP,
+    }
+}
+
+/// The looping tune, tracker style: each row is (melody, bass) as MIDI note
+/// numbers, 0 meaning the previous note goes on and 1 meaning silence.
+const TUNE: &[(u8, u8)] = &[
+    (69, 45), (0, 0), (72, 1), (76, 57), (74, 45), (0, 0), (72, 1), (69, 57),
+    (67, 43), (0, 0), (71, 1), (74, 55), (72, 43), (0, 0), (71, 1), (67, 55),
+    (65, 41), (0, 0), (69, 1), (72, 53), (71, 40), (0, 0), (72, 1), (74, 52),
+    (76, 45), (0, 0), (74, 1), (72, 57), (71, 44), (0, 1), (69, 52), (1, 1),
+];
+const TUNE_ROW_LENGTH: u32 = 150;
+const MUSIC_VOLUME: f32 = 0.12;
+
+fn midi_frequency(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+struct Voice {
+    notes: &'static [Note],
+    note: usize,
+    /// Samples played of the current note
+    position: u32,
+    phase: f32,
+}
+
+/// Generates the samples. Kept apart from SDL so it can be used and tested
+/// without an audio device.
+pub struct Synth {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    volume: f32,
+    muted: bool,
+    music_playing: bool,
+    music_position: u32,
+    melody_phase: f32,
+    bass_phase: f32,
+    noise: u32,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Synth {
+        Synth {
+            sample_rate,
+            voices: Vec::new(),
+            volume: 1.0,
+            muted: false,
+            music_playing: false,
+            music_position: 0,
+            melody_phase: 0.0,
+            bass_phase: 0.0,
+            noise: 0x1234_5678,
+        }
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        // A new turn replaces any turn still playing, so quick turns don't pile up
+        if sound == Sound::Turn {
+            self.voices.retain(|voice| voice.notes.as_ptr() != TURN.as_ptr());
+        }
+        self.voices.push(Voice { notes: notes(sound), note: 0, position: 0, phase: 0.0 });
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_music_playing(&mut self, playing: bool) {
+        if playing && !self.music_playing {
+            self.music_position = 0;
+        }
+        self.music_playing = playing;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let mut value = self.next_effect_sample();
+            if self.music_playing {
+                value += self.next_music_sample();
+            }
+            *sample = if self.muted { 0.0 } else { (value * self.volume).clamp(-1.0, 1.0) };
+        }
+    }
+
+    fn next_effect_sample(&mut self) -> f32 {
+        let sample_rate = self.sample_rate;
+        let mut noise = self.noise;
+        let mut value = 0.0;
+        for voice in &mut self.voices {
+            let note = voice.notes[voice.note];
+            let length = note.length * sample_rate / 1000;
+            let progress = voice.position as f32 / length as f32;
+            let frequency = note.from + (note.to - note.from) * progress;
+            voice.phase = (voice.phase + frequency / sample_rate as f32) % 1.0;
+            value += wave_sample(note.wave, voice.phase, &mut noise) * note.volume * (1.0 - progress);
+            voice.position += 1;
+            if voice.position >= length {
+                voice.position = 0;
+                voice.note += 1;
+            }
+        }
+        self.noise = noise;
+        self.voices.retain(|voice| voice.note < voice.notes.len());
+        value
+    }
+
+    fn next_music_sample(&mut self) -> f32 {
+        let row_samples = TUNE_ROW_LENGTH * self.sample_rate / 1000;
+        let row = (self.music_position / row_samples) as usize % TUNE.len();
+        // Find the note that is sounding on each channel
+        let sounding = |channel: fn(&(u8, u8)) -> u8| {
+            (0..TUNE.len()).map(|i| channel(&TUNE[(row + TUNE.len() - i) % TUNE.len()]))
+                .find(|&note| note != 0).unwrap_or(1)
+        };
+        let melody = sounding(|row| row.0);
+        let bass = sounding(|row| row.1);
+        self.music_position = (self.music_position + 1) % (row_samples * TUNE.len() as u32);
+
+        let mut noise = self.noise;
+        let mut value = 0.0;
+        if melody != 1 {
+            self.melody_phase = (self.melody_phase + midi_frequency(melody) / self.sample_rate as f32) % 1.0;
+            value += wave_sample(Wave::Pulse(2), self.melody_phase, &mut noise) * MUSIC_VOLUME;
+        }
+        if bass != 1 {
+            self.bass_phase = (self.bass_phase + midi_frequency(bass) / self.sample_rate as f32) % 1.0;
+            value += wave_sample(Wave::Triangle, self.bass_phase, &mut noise) * MUSIC_VOLUME * 1.5;
+        }
+        value
+    }
+}
+
+fn wave_sample(wave: Wave, phase: f32, noise: &mut u32) -> f32 {
+    match wave {
+        Wave::Pulse(duty) => if phase < duty as f32 / 8.0 { 1.0 } else { -1.0 },
+        Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        Wave::Noise => {
+            // xorshift; good enough for hiss
+            *noise ^= *noise << 13;
+            *noise ^= *noise >> 17;
+            *noise ^= *noise << 5;
+            (*noise as f32 / u32::MAX as f32) * 2.0 - 1.0
+        },
+    }
+}
+
+impl AudioCallback for Synth {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.fill(out);
+    }
+}
+
+/// The game's audio output. If no audio device can be opened the game just
+/// stays silent.
+pub struct Audio {
+    device: Option<AudioDevice<Synth>>,
+    muted: bool,
+}
+
+impl Audio {
+    pub fn new(ctx: &Sdl) -> Audio {
+        let device = ctx.audio().and_then(|audio| {
+            let spec = AudioSpecDesired { freq: Some(SAMPLE_RATE), channels: Some(1), samples: Some(1024) };
+            audio.open_playback(None, &spec, |spec| Synth::new(spec.freq as u32))
+        });
+        match device {
+            Ok(device) => {
+                device.resume();
+                Audio { device: Some(device), muted: false }
+            },
+            Err(e) => {
+                eprintln!("Could not open audio device, continuing without sound: {}", e);
+                Audio { device: None, muted: false }
+            }
+        }
+    }
+
+    fn with_synth<F: FnOnce(&mut Synth)>(&mut self, f: F) {
+        if let Some(ref mut device) = self.device {
+            f(&mut device.lock());
+        }
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        self.with_synth(|synth| synth.play(sound));
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.with_synth(|synth| synth.set_volume(volume));
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.with_synth(|synth| synth.set_muted(muted));
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn toggle_muted(&mut self) -> bool {
+        let muted = !self.muted;
+        self.set_muted(muted);
+        muted
+    }
+
+    pub fn set_music_playing(&mut self, playing: bool) {
+        self.with_synth(|synth| synth.set_music_playing(playing));
+    }
+
+    /// Plays the sound that goes with a game event, if any.
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::AppleEaten { .. } => self.play(Sound::Eat),
+            GameEvent::Turned { .. } => self.play(Sound::Turn),
+            GameEvent::Died { .. } => self.play(Sound::Death),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_play_and_end() {
+        let mut synth = Synth::new(8000);
+        let mut buffer = vec![0.0; 8000];
+        synth.fill(&mut buffer);
+        assert!(buffer.iter().all(|&sample| sample == 0.0));
+
+        synth.play(Sound::Eat);
+        synth.fill(&mut buffer);
+        assert!(buffer[..800].iter().any(|&sample| sample != 0.0));
+        // The eat sound is 100 ms long
+        assert!(buffer[800..].iter().all(|&sample| sample == 0.0));
+        assert!(synth.voices.is_empty());
+    }
+
+    #[test]
+    fn mute_and_volume() {
+        let mut synth = Synth::new(8000);
+        let mut buffer = vec![0.0; 400];
+        synth.set_music_playing(true);
+        synth.set_muted(true);
+        synth.fill(&mut buffer);
+        assert!(buffer.iter().all(|&sample| sample == 0.0));
+
+        synth.set_muted(false);
+        synth.set_volume(2.0);
+        assert_eq!(synth.volume(), 1.0);
+        synth.fill(&mut buffer);
+        assert!(buffer.iter().any(|&sample| sample != 0.0));
+        assert!(buffer.iter().all(|&sample| sample.abs() <= 1.0));
+    }
+
+    #[test]
+    fn opens_with_dummy_driver() {
+        ::std::env::set_var("SDL_AUDIODRIVER", "dummy");
+        let ctx = ::sdl2::init().expect("Initializing SDL 2");
+        let mut audio = Audio::new(&ctx);
+        assert!(audio.device.is_some());
+        audio.set_music_playing(true);
+        audio.play(Sound::MenuBlip);
+        audio.handle(&GameEvent::Died { cause: ::game::DeathCause::Wall });
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use constants::*;
+use game::Rules;
+use input::{Action, Bindings, ControlScheme};
+use speed::SpeedCurve;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArenaSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl ArenaSize {
+    pub fn all() -> Vec<ArenaSize> {
+        vec![ArenaSize::Small, ArenaSize::Medium, ArenaSize::Large]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArenaSize::Small => "Small",
+            ArenaSize::Medium => "Medium",
+            ArenaSize::Large => "Large",
+        }
+    }
+
+    /// Width and height in blocks. The large arena fills the whole window.
+    pub fn blocks(self) -> (u32, u32) {
+        match self {
+            ArenaSize::Small => (12, 9),
+            ArenaSize::Medium => (16, 12),
+            ArenaSize::Large => (ARENA_WIDTH_BLOCKS, ARENA_HEIGHT_BLOCKS),
+        }
+    }
+}
+
+/// Everything that can be changed on the options screen. Saved in
+/// `CONFIG_FILE` as `key = value` lines.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Settings {
+    pub speed_curve: SpeedCurve,
+    pub arena_size: ArenaSize,
+    pub wrap: bool,
+    /// From 1 to `MAX_APPLES`
+    pub apple_count: u32,
+    /// Name of the theme
+    pub theme: String,
+    /// From 0 to `MAX_VOLUME`
+    pub volume: u32,
+    pub fullscreen: bool,
+    pub control_scheme: ControlScheme,
+    /// Saved as a `key_<action>` line for each action
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            speed_curve: SpeedCurve::Linear,
+            arena_size: ArenaSize::Large,
+            wrap: false,
+            apple_count: 1,
+            theme: "Classic".to_string(),
+            volume: MAX_VOLUME,
+            fullscreen: false,
+            control_scheme: ControlScheme::Absolute,
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+/// Finds the item whose name matches `value`, ignoring case.
+fn by_name<T: Copy, F: Fn(T) -> &'static str>(all: Vec<T>, name: F, value: &str) -> Option<T> {
+    all.into_iter().find(|&item| name(item).eq_ignore_ascii_case(value))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+impl Settings {
+    pub fn rules(&self) -> Rules {
+        let (width, height) = self.arena_size.blocks();
+        let mut rules = Rules::new(width, height);
+        rules.wrap = self.wrap;
+        rules.speed_curve = self.speed_curve;
+        rules.apple_count = self.apple_count;
+        rules
+    }
+
+    /// Loads the settings, falling back to the defaults if there is no
+    /// config file or it can't be read.
+    pub fn load() -> Settings {
+        let mut text = String::new();
+        if File::open(CONFIG_FILE).and_then(|mut f| f.read_to_string(&mut text)).is_err() {
+            return Settings::default();
+        }
+        Settings::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Could not read {}, using default settings: {}", CONFIG_FILE, e);
+            Settings::default()
+        })
+    }
+
+    /// Parses a config file in the same format as theme files. Keys that
+    /// are left out keep their default values.
+    pub fn parse(text: &str) -> Result<Settings, ConfigError> {
+        let mut settings = Settings::default();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(ConfigError::Syntax(line_number)),
+            };
+            let bad_value = || ConfigError::BadValue(line_number, value.to_string());
+            match key {
+                "speed_curve" => settings.speed_curve = by_name(SpeedCurve::all(), SpeedCurve::name, value).ok_or_else(bad_value)?,
+                "arena_size" => settings.arena_size = by_name(ArenaSize::all(), ArenaSize::name, value).ok_or_else(bad_value)?,
+                "wrap" => settings.wrap = parse_bool(value).ok_or_else(bad_value)?,
+                "apples" => settings.apple_count = value.parse().ok().filter(|n| (1..=MAX_APPLES).contains(n)).ok_or_else(bad_value)?,
+                "theme" => settings.theme = value.to_string(),
+                "volume" => settings.volume = value.parse().ok().filter(|&v| v <= MAX_VOLUME).ok_or_else(bad_value)?,
+                "fullscreen" => settings.fullscreen = parse_bool(value).ok_or_else(bad_value)?,
+                "control_scheme" => settings.control_scheme = by_name(ControlScheme::all(), ControlScheme::name, value).ok_or_else(bad_value)?,
+                _ => {
+                    let action = Action::all().into_iter().find(|action| key == format!("key_{}", action.config_name()))
+                        .ok_or_else(|| ConfigError::UnknownKey(line_number, key.to_string()))?;
+                    if !settings.bindings.parse_keys(action, value) {
+                        return Err(bad_value());
+                    }
+                },
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut f = File::create(CONFIG_FILE)?;
+        write!(f, "{}", self.to_config())
+    }
+
+    fn to_config(&self) -> String {
+        let mut config = format!("speed_curve = {}\narena_size = {}\nwrap = {}\napples = {}\ntheme = {}\nvolume = {}\nfullscreen = {}\ncontrol_scheme = {}\n",
+            self.speed_curve.name().to_lowercase(),
+            self.arena_size.name().to_lowercase(),
+            on_off(self.wrap),
+            self.apple_count,
+            self.theme,
+            self.volume,
+            on_off(self.fullscreen),
+            self.control_scheme.name().to_lowercase());
+        for action in Action::all() {
+            config.push_str(&format!("key_{} = {}\n", action.config_name(), self.bindings.format_keys(action)));
+        }
+        config
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Syntax(usize),
+    UnknownKey(usize, String),
+    BadValue(usize, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Syntax(line) => write!(f, "line {}: expected key = value", line),
+            ConfigError::UnknownKey(line, ref key) => write!(f, "line {}: unknown key {}", line, key),
+            ConfigError::BadValue(line, ref value) => write!(f, "line {}: bad value {}", line, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::KeyPreset;
+
+    #[test]
+    fn round_trip() {
+        let settings = Settings {
+            speed_curve: SpeedCurve::Constant,
+            arena_size: ArenaSize::Small,
+            wrap: true,
+            apple_count: 3,
+            theme: "High contrast".to_string(),
+            volume: 3,
+            fullscreen: true,
+            control_scheme: ControlScheme::Relative,
+            bindings: Bindings::preset(KeyPreset::Hjkl),
+        };
+        assert_eq!(Settings::parse(&settings.to_config()).unwrap(), settings);
+    }
+
+    #[test]
+    fn parse_errors() {
+        match Settings::parse("volume = 99") {
+            Err(ConfigError::BadValue(1, _)) => {},
+            other => panic!("Unexpected {:?}", other),
+        }
+        match Settings::parse("# Comment\nspeed = fast") {
+            Err(ConfigError::UnknownKey(2, _)) => {},
+            other => panic!("Unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::distributions::{IndependentSample, Range};
+
+use constants::*;
+use game::{self, Rules};
+use speed::SpeedCurve;
+
+/// A day in UTC, so that everyone gets the same board at the same time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        Date::from_days(seconds / 86400)
+    }
+
+    /// The date `days` days after 1970-01-01, from Howard Hinnant's
+    /// `civil_from_days`.
+    pub fn from_days(days: u64) -> Date {
+        // Counted in 400-year eras starting from March 0000, so that leap
+        // days come last in their year
+        let days = days + 719468;
+        let era = days / 146097;
+        let day_of_era = days % 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year: year as u32, month: month as u32, day: day as u32 }
+    }
+
+    /// Like 20261019.
+    pub fn seed(self) -> u32 {
+        self.year * 10000 + self.month * 100 + self.day
+    }
+
+    /// The date a daily board was made for, back from its seed.
+    pub fn from_seed(seed: u32) -> Date {
+        Date { year: seed / 10000, month: seed / 100 % 100, day: seed % 100 }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The board and rules of the day. Everything comes from the date, none of
+/// it from the options.
+pub fn rules(date: Date) -> Rules {
+    let mut rng = game::seeded_rng(date.seed());
+    let mut rules = Rules::new(ARENA_WIDTH_BLOCKS, ARENA_HEIGHT_BLOCKS);
+    let curves = SpeedCurve::all();
+    rules.speed_curve = curves[Range::new(0, curves.len()).ind_sample(&mut rng)];
+    rules.apple_count = Range::new(1, 4).ind_sample(&mut rng);
+    rules.wrap = Range::new(0, 4).ind_sample(&mut rng) == 0;
+    rules.seed = Some(date.seed());
+
+    let (width, height) = (rules.arena_width, rules.arena_height);
+    let start = (width / 2, height / 2);
+    for _ in 0..Range::new(DAILY_MIN_OBSTACLES, DAILY_MAX_OBSTACLES + 1).ind_sample(&mut rng) {
+        let length = Range::new(2, DAILY_MAX_OBSTACLE_LENGTH + 1).ind_sample(&mut rng);
+        let (dx, dy) = if Range::new(0, 2).ind_sample(&mut rng) == 0 { (1, 0) } else { (0, 1) };
+        // Two blocks clear of the edges, so that nothing gets walled in
+        let x = Range::new(2, width - 2 - dx * (length - 1)).ind_sample(&mut rng);
+        let y = Range::new(2, height - 2 - dy * (length - 1)).ind_sample(&mut rng);
+        let blocks: Vec<_> = (0..length).map(|i| (x + dx * i, y + dy * i)).collect();
+        // The worm starts off going right along its row
+        let in_the_way = |&(x, y): &(u32, u32)| y == start.1 || (x == start.0 && y.abs_diff(start.1) <= 1);
+        let touches = |&(x, y): &(u32, u32)| rules.obstacles.iter()
+            .any(|&(other_x, other_y)| x.abs_diff(other_x) <= 1 && y.abs_diff(other_y) <= 1);
+        if !blocks.iter().any(|block| in_the_way(block) || touches(block)) {
+            rules.obstacles.extend(blocks);
+        }
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(11016).to_string(), "2000-02-29");
+        let date = Date::from_days(20745);
+        assert_eq!(date, Date { year: 2026, month: 10, day: 19 });
+        assert_eq!(date.seed(), 20261019);
+        assert_eq!(Date::from_seed(date.seed()), date);
+    }
+
+    #[test]
+    fn same_board_all_day() {
+        let date = Date { year: 2026, month: 10, day: 19 };
+        let rules = rules(date);
+        assert_eq!(rules, super::rules(date));
+        assert_ne!(rules, super::rules(Date { day: 20, ..date }));
+        assert!(!rules.obstacles.is_empty());
+        assert!(rules.obstacles.iter().all(|&(x, y)| x >= 2 && x < rules.arena_width - 2 && y >= 2 && y < rules.arena_height - 2));
+        assert!(rules.obstacles.iter().all(|&(_, y)| y != rules.arena_height / 2));
+    }
+}
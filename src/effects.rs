@@ -0,0 +1,128 @@
+use rand::{ThreadRng, thread_rng, Rng};
+use sdl2::rect::Rect;
+
+use constants::*;
+use game::GameEvent;
+use renderer::Renderer;
+use text::GlyphAtlas;
+use theme::Theme;
+
+struct Particle {
+    x: f32,
+    y: f32,
+    dx: f32,
+    dy: f32,
+    age: u32,
+}
+
+struct Popup {
+    text: String,
+    x: i32,
+    y: i32,
+    age: u32,
+}
+
+/// Short lived decorations that react to game events: particles bursting
+/// from eaten apples, floating score popups and screen shake on death.
+/// Positions are in pixels, relative to the game viewport.
+pub struct Effects {
+    rng: ThreadRng,
+    particles: Vec<Particle>,
+    popups: Vec<Popup>,
+    shake_left: u32,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects {
+            rng: thread_rng(),
+            particles: Vec::new(),
+            popups: Vec::new(),
+            shake_left: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.popups.clear();
+        self.shake_left = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.particles.is_empty() || !self.popups.is_empty() || self.shake_left > 0
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::AppleEaten { pos, points, .. } => {
+                let center_x = (pos.0 * BLOCK_SIZE + BLOCK_SIZE / 2) as f32;
+                let center_y = (pos.1 * BLOCK_SIZE + BLOCK_SIZE / 2) as f32;
+                for _ in 0..PARTICLE_COUNT {
+                    let angle = self.rng.gen_range(0.0, ::std::f32::consts::PI * 2.0);
+                    let speed = self.rng.gen_range(0.03, 0.12);
+                    self.particles.push(Particle {
+                        x: center_x,
+                        y: center_y,
+                        dx: angle.cos() * speed,
+                        dy: angle.sin() * speed,
+                        age: 0,
+                    });
+                }
+                if points > 0 {
+                    self.popups.push(Popup {
+                        text: format!("+{}", points),
+                        x: center_x as i32,
+                        y: (pos.1 * BLOCK_SIZE) as i32,
+                        age: 0,
+                    });
+                }
+            },
+            GameEvent::Died { .. } => {
+                self.shake_left = SHAKE_DURATION;
+            },
+            _ => {},
+        }
+    }
+
+    /// Advances the effects by `time_diff` milliseconds.
+    pub fn update(&mut self, time_diff: u32) {
+        for particle in &mut self.particles {
+            particle.age += time_diff;
+            particle.x += particle.dx * time_diff as f32;
+            particle.y += particle.dy * time_diff as f32;
+            particle.dy += PARTICLE_GRAVITY * time_diff as f32;
+        }
+        self.particles.retain(|particle| particle.age < PARTICLE_LIFETIME);
+        for popup in &mut self.popups {
+            popup.age += time_diff;
+        }
+        self.popups.retain(|popup| popup.age < POPUP_LIFETIME);
+        self.shake_left = self.shake_left.saturating_sub(time_diff);
+    }
+
+    /// How much the game viewport should be moved to shake it.
+    pub fn shake_offset(&self) -> (i32, i32) {
+        if self.shake_left == 0 {
+            return (0, 0);
+        }
+        // Fading, jittery back and forth motion; deterministic so that redraws don't jump
+        let amplitude = (SHAKE_AMPLITUDE * self.shake_left / SHAKE_DURATION) as i32 + 1;
+        let phase = self.shake_left / 16;
+        let x = if phase.is_multiple_of(2) { amplitude } else { -amplitude };
+        let y = if phase.is_multiple_of(3) { amplitude / 2 } else { -amplitude / 2 };
+        (x, y)
+    }
+
+    /// Draws particles and popups; the game viewport should be set up already.
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme) {
+        for particle in &self.particles {
+            let size = 1 + 3 * (PARTICLE_LIFETIME - particle.age) / PARTICLE_LIFETIME;
+            renderer.fill_rect(Rect::new(particle.x as i32, particle.y as i32, size, size), theme.apple);
+        }
+        for popup in &self.popups {
+            let rise = (POPUP_RISE * popup.age / POPUP_LIFETIME) as i32;
+            let mut glyphs = atlas.text_rects_centered(&popup.text[..], (popup.x, popup.y - rise), 1);
+            renderer.draw_glyphs(atlas, &mut glyphs, theme.points);
+        }
+    }
+}
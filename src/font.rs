@@ -0,0 +1,316 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use text::{GlyphAtlas, GLYPH_COUNT};
+
+const PSF1_MAGIC: &[u8] = &[0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF2_MAGIC: &[u8] = &[0x72, 0xb5, 0x4a, 0x86];
+const BDF_MAGIC: &[u8] = b"STARTFONT";
+/// Glyphs larger than this either way are most likely a broken file.
+const MAX_GLYPH_SIZE: u32 = 32;
+/// Only the first `GLYPH_COUNT` glyphs get used, but PSF2 fonts can have more.
+const MAX_GLYPH_COUNT: u32 = 4096;
+
+/// Loads a bitmap font. The format is told by its contents: PSF1, PSF2 and
+/// BDF files start with their own magic, and anything else is taken to be a
+/// raw DOS font of 256 glyphs, 8 pixels wide. For raw fonts named like
+/// `*.F14` the number in the extension has to match the glyph height.
+pub fn load(path: &Path) -> Result<GlyphAtlas, FontError> {
+    let mut data = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut data)).map_err(|e| FontError::Io(e.to_string()))?;
+    let height = path.extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() == 3 && ext.starts_with(['F', 'f']))
+        .and_then(|ext| ext[1..].parse().ok());
+    parse(&data, height)
+}
+
+/// Parses a font from memory, see `load`. `raw_height` is the glyph height
+/// a raw font is expected to have, if known.
+pub fn parse(data: &[u8], raw_height: Option<u32>) -> Result<GlyphAtlas, FontError> {
+    if data.starts_with(PSF2_MAGIC) {
+        parse_psf2(data)
+    } else if data.starts_with(PSF1_MAGIC) {
+        parse_psf1(data)
+    } else if data.starts_with(BDF_MAGIC) {
+        parse_bdf(data)
+    } else {
+        parse_raw(data, raw_height)
+    }
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), FontError> {
+    if width == 0 || height == 0 || width > MAX_GLYPH_SIZE || height > MAX_GLYPH_SIZE {
+        Err(FontError::Unsupported("glyphs have to be from 1x1 to 32x32 pixels"))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_raw(data: &[u8], height: Option<u32>) -> Result<GlyphAtlas, FontError> {
+    if data.is_empty() || !data.len().is_multiple_of(GLYPH_COUNT as usize) {
+        return Err(FontError::UnknownFormat);
+    }
+    let found = (data.len() / GLYPH_COUNT as usize) as u32;
+    if height.is_some_and(|height| height != found) {
+        return Err(FontError::Corrupt("size doesn't match the height in the file name"));
+    }
+    check_size(8, found)?;
+    Ok(GlyphAtlas::from_bitmap(data, 8, GLYPH_COUNT))
+}
+
+fn parse_psf1(data: &[u8]) -> Result<GlyphAtlas, FontError> {
+    if data.len() < 4 {
+        return Err(FontError::Truncated);
+    }
+    let count = if data[2] & PSF1_MODE_512 != 0 { 512 } else { 256 };
+    let height = data[3] as u32;
+    check_size(8, height)?;
+    let glyphs = data.get(4..4 + (count * height) as usize).ok_or(FontError::Truncated)?;
+    Ok(GlyphAtlas::from_bitmap(glyphs, 8, count))
+}
+
+fn parse_psf2(data: &[u8]) -> Result<GlyphAtlas, FontError> {
+    let field = |i: usize| data.get(i * 4..i * 4 + 4)
+        .map(|bytes| u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24)
+        .ok_or(FontError::Truncated);
+    let (header_size, count, glyph_size, height, width) = (field(2)?, field(4)?, field(5)?, field(6)?, field(7)?);
+    check_size(width, height)?;
+    if glyph_size != width.div_ceil(8) * height {
+        return Err(FontError::Corrupt("glyph size doesn't match the width and height"));
+    }
+    if count == 0 {
+        return Err(FontError::Corrupt("no glyphs"));
+    }
+    if count > MAX_GLYPH_COUNT {
+        return Err(FontError::Corrupt("too many glyphs"));
+    }
+    let size = count.checked_mul(glyph_size).ok_or(FontError::Corrupt("glyphs too large"))?;
+    let start = header_size as usize;
+    let glyphs = data.get(start..start.saturating_add(size as usize)).ok_or(FontError::Truncated)?;
+    Ok(GlyphAtlas::from_bitmap(glyphs, width, count))
+}
+
+/// The glyph being read from a BDF file.
+struct BdfGlyph {
+    encoding: Option<u32>,
+    /// Width, height and the offset of the bottom left corner from the origin
+    bbx: Option<(u32, u32, i32, i32)>,
+    /// How far the origin moves after this glyph
+    advance: Option<u32>,
+    rows: Option<Vec<Vec<u8>>>,
+}
+
+/// Reads the glyphs with encodings 0-255 into cells the size of the font's
+/// bounding box, each placed by its own bounding box. The rest are skipped.
+/// Proportional text uses the advance widths from the file, less the gap the
+/// atlas puts between glyphs.
+fn parse_bdf(data: &[u8]) -> Result<GlyphAtlas, FontError> {
+    let text = String::from_utf8_lossy(data);
+    let mut font_bbx: Option<(u32, u32, i32, i32)> = None;
+    let mut bits = Vec::new();
+    let mut glyph: Option<BdfGlyph> = None;
+    let mut advances = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |what| FontError::Bdf(line_number, what);
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or("");
+        let numbers = || words.clone().map(|word| word.parse::<i32>().map_err(|_| error("bad number"))).collect::<Result<Vec<_>, _>>();
+        let offsets = -(MAX_GLYPH_SIZE as i32)..=MAX_GLYPH_SIZE as i32;
+        let bbx = || match numbers()?[..] {
+            [_, _, x, y] if !offsets.contains(&x) || !offsets.contains(&y) => Err(error("offsets too far from the origin")),
+            [width, height, x, y] if width >= 0 && height >= 0 => Ok((width as u32, height as u32, x, y)),
+            _ => Err(error("expected width, height and offsets")),
+        };
+        // Bitmap rows until ENDCHAR
+        if let Some(rows) = glyph.as_mut().and_then(|glyph| glyph.rows.as_mut()) {
+            if keyword != "ENDCHAR" {
+                let hex = line.trim();
+                if !hex.is_ascii() {
+                    return Err(error("bad bitmap row"));
+                }
+                let row = (0..hex.len() / 2)
+                    .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| error("bad bitmap row"))?;
+                rows.push(row);
+                continue;
+            }
+        }
+        match keyword {
+            "FONTBOUNDINGBOX" => {
+                let (width, height, x, y) = bbx()?;
+                check_size(width, height)?;
+                font_bbx = Some((width, height, x, y));
+                bits = vec![0; (width.div_ceil(8) * height * GLYPH_COUNT) as usize];
+            },
+            "STARTCHAR" => {
+                if font_bbx.is_none() {
+                    return Err(error("glyph before FONTBOUNDINGBOX"));
+                }
+                glyph = Some(BdfGlyph { encoding: None, bbx: None, advance: None, rows: None });
+            },
+            "ENCODING" | "DWIDTH" | "BBX" | "BITMAP" | "ENDCHAR" => {
+                let current = glyph.as_mut().ok_or_else(|| error("outside of a glyph"))?;
+                match keyword {
+                    // -1 is for glyphs without a standard encoding
+                    "ENCODING" => current.encoding = numbers()?.first().and_then(|&encoding| if encoding >= 0 { Some(encoding as u32) } else { None }),
+                    "DWIDTH" => current.advance = numbers()?.first().map(|&advance| advance.max(0) as u32),
+                    "BBX" => current.bbx = Some(bbx()?),
+                    "BITMAP" => current.rows = Some(Vec::new()),
+                    _ => {
+                        let font_bbx = font_bbx.expect("checked at STARTCHAR");
+                        let done = glyph.take().expect("checked above");
+                        let bbx = done.bbx.ok_or_else(|| error("glyph without BBX"))?;
+                        let rows = done.rows.ok_or_else(|| error("glyph without BITMAP"))?;
+                        if rows.len() != bbx.1 as usize || rows.iter().any(|row| row.len() < bbx.0.div_ceil(8) as usize) {
+                            return Err(error("bitmap doesn't match BBX"));
+                        }
+                        if let Some(encoding) = done.encoding.filter(|&encoding| encoding < GLYPH_COUNT) {
+                            draw_bdf_glyph(&mut bits, font_bbx, encoding, bbx, &rows);
+                            if let Some(advance) = done.advance {
+                                advances.push((encoding as u8, advance));
+                            }
+                        }
+                    },
+                }
+            },
+            _ => {},
+        }
+    }
+    let (width, _, x, _) = font_bbx.ok_or(FontError::Bdf(0, "no FONTBOUNDINGBOX"))?;
+    let mut atlas = GlyphAtlas::from_bitmap(&bits, width, GLYPH_COUNT);
+    for (glyph, advance) in advances {
+        // The origin is as far right of the cell's left edge as the box is left of the origin
+        atlas.set_span(glyph, (-x).max(0) as u32, advance.saturating_sub(1));
+    }
+    Ok(atlas)
+}
+
+/// Copies a glyph's bitmap into its cell, clipping whatever sticks out.
+fn draw_bdf_glyph(bits: &mut [u8], font_bbx: (u32, u32, i32, i32), encoding: u32, bbx: (u32, u32, i32, i32), rows: &[Vec<u8>]) {
+    let (cell_width, cell_height, font_x, font_y) = font_bbx;
+    let bytes_per_row = cell_width.div_ceil(8) as usize;
+    // The font's bounding box has its bottom edge at font_y below the baseline
+    let top = (cell_height as i32 + font_y) - (bbx.3 + bbx.1 as i32);
+    let left = bbx.2 - font_x;
+    for (row_index, row) in rows.iter().enumerate() {
+        let y = top + row_index as i32;
+        if y < 0 || y >= cell_height as i32 {
+            continue;
+        }
+        for x_in_glyph in 0..bbx.0 as usize {
+            let x = left + x_in_glyph as i32;
+            if x < 0 || x >= cell_width as i32 || row[x_in_glyph / 8] & (0b1000_0000 >> (x_in_glyph % 8)) == 0 {
+                continue;
+            }
+            let i = (encoding * cell_height + y as u32) as usize * bytes_per_row + x as usize / 8;
+            bits[i] |= 0b1000_0000 >> (x % 8);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(String),
+    UnknownFormat,
+    Truncated,
+    Unsupported(&'static str),
+    Corrupt(&'static str),
+    /// A problem on a line of a BDF file, or with the whole file on line 0.
+    Bdf(usize, &'static str),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FontError::Io(ref e) => write!(f, "{}", e),
+            FontError::UnknownFormat => write!(f, "not a PSF, BDF or raw 8xN font"),
+            FontError::Truncated => write!(f, "file ends too soon"),
+            FontError::Unsupported(what) => write!(f, "unsupported font: {}", what),
+            FontError::Corrupt(what) => write!(f, "corrupt font: {}", what),
+            FontError::Bdf(0, what) => write!(f, "{}", what),
+            FontError::Bdf(line, what) => write!(f, "line {}: {}", line, what),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILT_IN: &[u8] = include_bytes!("../COMPUTER.F14");
+
+    /// The pixels of glyph `glyph` as strings of `#` and `.`.
+    fn glyph(atlas: &GlyphAtlas, glyph: u8) -> Vec<String> {
+        let rect = atlas.glyph_rect(glyph);
+        (0..rect.height()).map(|y| {
+            (0..rect.width()).map(|x| if atlas.pixel(x, rect.y() as u32 + y) { '#' } else { '.' }).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn raw_and_psf() {
+        let raw = parse(BUILT_IN, Some(14)).unwrap();
+        assert_eq!(glyph(&raw, b'A'), glyph(&GlyphAtlas::new(), b'A'));
+        assert!(matches!(parse(BUILT_IN, Some(16)), Err(FontError::Corrupt(_))));
+        assert!(matches!(parse(&BUILT_IN[1..], None), Err(FontError::UnknownFormat)));
+
+        let mut psf1 = vec![0x36, 0x04, 0, 14];
+        psf1.extend_from_slice(BUILT_IN);
+        let psf1 = parse(&psf1, None).unwrap();
+        assert_eq!((psf1.glyph_width(), psf1.glyph_height(), psf1.glyph_count()), (8, 14, 256));
+        assert_eq!(glyph(&psf1, b'A'), glyph(&raw, b'A'));
+        assert!(matches!(parse(&[0x36, 0x04, 1, 14, 0], None), Err(FontError::Truncated)));
+
+        // Two 10x3 glyphs, two bytes per row
+        let mut psf2 = PSF2_MAGIC.to_vec();
+        for &field in &[0u32, 32, 0, 2, 6, 3, 10] {
+            psf2.extend_from_slice(&[field as u8, (field >> 8) as u8, (field >> 16) as u8, (field >> 24) as u8]);
+        }
+        psf2.extend_from_slice(&[0xff, 0xc0, 0, 0, 0xff, 0xc0, 0x80, 0x40, 0x80, 0x40, 0x80, 0x40]);
+        let psf2_font = parse(&psf2, None).unwrap();
+        assert_eq!((psf2_font.glyph_width(), psf2_font.glyph_height(), psf2_font.glyph_count()), (10, 3, 2));
+        assert_eq!(glyph(&psf2_font, 0), vec!["##########", "..........", "##########"]);
+        assert_eq!(glyph(&psf2_font, 1), vec!["#........#"; 3]);
+        // Glyphs past the end of the font fall back to the first one
+        assert_eq!(glyph(&psf2_font, b'A'), glyph(&psf2_font, 0));
+        psf2[20] = 7;
+        assert!(matches!(parse(&psf2, None), Err(FontError::Corrupt(_))));
+        psf2[20] = 6;
+        // Glyph counts too large to be real, like ones whose size overflows
+        psf2[16..20].copy_from_slice(&[0xff; 4]);
+        assert!(matches!(parse(&psf2, None), Err(FontError::Corrupt(_))));
+        psf2[16..20].copy_from_slice(&[0, 0, 0, 0x40]);
+        assert!(matches!(parse(&psf2, None), Err(FontError::Corrupt(_))));
+    }
+
+    #[test]
+    fn bdf() {
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 5 6 0 -1\nCHARS 2\n\
+            STARTCHAR A\nENCODING 65\nDWIDTH 4 0\nBBX 3 3 1 0\nBITMAP\n40\nA0\nE0\nENDCHAR\n\
+            STARTCHAR big\nENCODING 9731\nBBX 5 6 0 -1\nBITMAP\nF8\nF8\nF8\nF8\nF8\nF8\nENDCHAR\nENDFONT\n";
+        let font = parse(bdf.as_bytes(), None).unwrap();
+        assert_eq!((font.glyph_width(), font.glyph_height(), font.glyph_count()), (5, 6, 256));
+        assert_eq!(glyph(&font, b'A'), vec![".....", ".....", "..#..", ".#.#.", ".###.", "....."]);
+        assert_eq!(font.span_rect(b'A').width(), 3);
+
+        let broken = bdf.replace("A0", "A0\nE0");
+        assert!(matches!(parse(broken.as_bytes(), None), Err(FontError::Bdf(13, _))));
+        assert!(matches!(parse(b"STARTFONT 2.1\nSTARTCHAR A\n", None), Err(FontError::Bdf(2, _))));
+        assert!(matches!(parse(b"STARTFONT 2.1\nFONTBOUNDINGBOX 64 8 0 0\n", None), Err(FontError::Unsupported(_))));
+
+        let non_ascii = bdf.replace("\n40\n", "\n\u{e9}0\n");
+        assert!(matches!(parse(non_ascii.as_bytes(), None), Err(FontError::Bdf(9, _))));
+        let far_up = bdf.replace("FONTBOUNDINGBOX 5 6 0 -1", "FONTBOUNDINGBOX 5 6 0 2147483647");
+        assert!(matches!(parse(far_up.as_bytes(), None), Err(FontError::Bdf(2, _))));
+        let far_left = bdf.replace("FONTBOUNDINGBOX 5 6 0 -1", "FONTBOUNDINGBOX 5 6 -2147483648 -1");
+        assert!(matches!(parse(far_left.as_bytes(), None), Err(FontError::Bdf(2, _))));
+        let far_right = bdf.replace("BBX 3 3 1 0", "BBX 3 3 2147483647 0");
+        assert!(matches!(parse(far_right.as_bytes(), None), Err(FontError::Bdf(7, _))));
+    }
+}
@@ -1,10 +1,12 @@
 
-use rand::{ThreadRng, thread_rng};
+use rand::{Rng, SeedableRng, XorShiftRng, thread_rng};
 use rand::distributions::{IndependentSample, Range};
 
 use constants::*;
+use scoring::{self, Meal, ScoreBreakdown, ScoreRule};
+use speed::SpeedCurve;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Direction {
     Up,
     Down,
@@ -12,23 +14,234 @@ pub enum Direction {
     Right,
 }
 
+impl Direction {
+    pub fn turned_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turned_right(self) -> Direction {
+        self.turned_left().turned_left().turned_left()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AppleKind {
+    Normal,
+    /// Worth `GOLDEN_APPLE_MULTIPLIER` times the points, but only there for
+    /// `GOLDEN_APPLE_LIFETIME` milliseconds.
+    Golden,
+    /// Takes `POISON_SHRINK` segments off the worm instead of growing it.
+    Poison,
+    /// Takes the speed back down by `SLOW_APPLE_STEPS` apples' worth.
+    Slow,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Apple {
+    pub pos: (u32, u32),
+    pub kind: AppleKind,
+    /// Milliseconds until the apple goes away, for apples that do.
+    pub time_left: Option<u32>,
+}
+
+impl Apple {
+    pub fn new(pos: (u32, u32), kind: AppleKind) -> Apple {
+        let time_left = if kind == AppleKind::Golden { Some(GOLDEN_APPLE_LIFETIME) } else { None };
+        Apple { pos, kind, time_left }
+    }
+}
+
+/// Pickups that change the rules for a number of steps.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUpKind {
+    /// The worm can go through itself.
+    Ghost,
+    /// Steps take `SLOW_MOTION_FACTOR` times as long.
+    SlowMotion,
+    /// Apples are worth `POWER_UP_MULTIPLIER` times the points.
+    Multiplier,
+    /// The worm goes through walls and comes out on the other side, and
+    /// passes over obstacles.
+    WallPhase,
+}
+
+impl PowerUpKind {
+    pub fn all() -> Vec<PowerUpKind> {
+        vec![PowerUpKind::Ghost, PowerUpKind::SlowMotion, PowerUpKind::Multiplier, PowerUpKind::WallPhase]
+    }
+
+    /// Short name for the status bar.
+    pub fn tag(self) -> &'static str {
+        match self {
+            PowerUpKind::Ghost => "GHOST",
+            PowerUpKind::SlowMotion => "SLOW",
+            PowerUpKind::Multiplier => "X2",
+            PowerUpKind::WallPhase => "PHASE",
+        }
+    }
+
+    /// How many steps the effect lasts.
+    pub fn duration(self) -> u32 {
+        match self {
+            PowerUpKind::Ghost => 30,
+            PowerUpKind::SlowMotion => 20,
+            PowerUpKind::Multiplier => 40,
+            PowerUpKind::WallPhase => 30,
+        }
+    }
+}
+
+/// A power-up waiting in the arena to be picked up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PowerUp {
+    pub pos: (u32, u32),
+    pub kind: PowerUpKind,
+    /// Steps until it goes away if nobody takes it.
+    pub steps_left: u32,
+}
+
+/// A power-up that has been picked up and is in effect.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ActivePowerUp {
+    pub kind: PowerUpKind,
+    pub steps_left: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    Wall,
+    OwnBody,
+}
+
+/// Things that happened in the game, in the order they happened. They are
+/// queued up by `Game` for audio, effects and the like to react to, so that
+/// those don't need to compare the game's state between frames.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    /// The worm moved one block; `head` is where its head is now.
+    Stepped { head: (u32, u32) },
+    /// The worm turned to a new direction, just before stepping.
+    Turned { direction: Direction },
+    AppleEaten { pos: (u32, u32), kind: AppleKind, points: u32 },
+    /// The worm got one segment longer.
+    Grew { length: usize },
+    /// The worm lost segments to a poison apple.
+    Shrank { length: usize },
+    /// The time between steps changed, in milliseconds.
+    SpeedChanged { step_duration: u32 },
+    Died { cause: DeathCause },
+    /// The game ended by its rules, with the worm still alive.
+    Finished,
+    AppleSpawned { pos: (u32, u32), kind: AppleKind },
+    /// An apple ran out of time before it was eaten.
+    AppleExpired { pos: (u32, u32) },
+    PowerUpSpawned { pos: (u32, u32), kind: PowerUpKind },
+    /// A power-up was left lying around for too long.
+    PowerUpExpired { pos: (u32, u32) },
+    PowerUpTaken { pos: (u32, u32), kind: PowerUpKind },
+    /// The effect of a power-up wore off.
+    PowerUpEnded { kind: PowerUpKind },
+}
+
+/// The settings a game is played with.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rules {
+    pub arena_width: u32,
+    pub arena_height: u32,
+    /// The worm goes through the edges of the arena and comes out on the other side; there are no walls.
+    pub wrap: bool,
+    pub speed_curve: SpeedCurve,
+    /// How many apples there are in the arena at a time.
+    pub apple_count: u32,
+    pub scoring: Vec<ScoreRule>,
+    /// The game ends after this many milliseconds.
+    pub time_limit: Option<u32>,
+    /// The worm grows by itself every this many steps.
+    pub grow_every: Option<u32>,
+    /// The game ends when the worm is this long.
+    pub target_length: Option<usize>,
+    /// Seeds the random numbers, so that games with the same seed get the
+    /// same apples and power-ups as long as they are played the same way.
+    pub seed: Option<u32>,
+    /// Blocks of wall inside the arena.
+    pub obstacles: Vec<(u32, u32)>,
+}
+
+impl Rules {
+    pub fn new(arena_width: u32, arena_height: u32) -> Rules {
+        Rules {
+            arena_width,
+            arena_height,
+            wrap: false,
+            speed_curve: SpeedCurve::Linear,
+            apple_count: 1,
+            scoring: ScoreRule::defaults(),
+            time_limit: None,
+            grow_every: None,
+            target_length: None,
+            seed: None,
+            obstacles: Vec::new(),
+        }
+    }
+}
+
+/// A random number generator that gives the same numbers everywhere for the
+/// same seed. An all-zero seed isn't allowed, hence the constant in the mix.
+pub fn seeded_rng(seed: u32) -> XorShiftRng {
+    XorShiftRng::from_seed([seed, seed ^ 0x9e37_79b9, 0x6d61_746f, 1])
+}
+
 pub struct Game {
-    rng: ThreadRng,
+    rng: XorShiftRng,
+    rules: Rules,
     frame_time: u32,
     step_duration: u32,
+    elapsed: u32,
+    apples_eaten: u32,
+    /// Apples eaten in a row quickly enough for the combo rule
+    combo: u32,
+    steps_since_meal: u32,
+    /// Where the head was when the last apple was eaten, or where it started
+    meal_start: (u32, u32),
+    has_eaten: bool,
+    breakdown: ScoreBreakdown,
     pub arena_width: u32,
     pub arena_height: u32,
     pub worm: Vec<(u32, u32)>,
     pub direction: Direction,
     pub new_direction: Option<Direction>,
     pub grow: bool,
-    pub apples: Vec<(u32, u32)>,
+    pub apples: Vec<Apple>,
+    pub power_ups: Vec<PowerUp>,
+    /// The power-ups in effect, in the order they were picked up. Taking one
+    /// that is already in effect starts its countdown over.
+    pub active: Vec<ActivePowerUp>,
     pub points: u32,
     pub alive: bool,
+    /// Ended by the rules rather than by dying, see `Rules`.
+    pub finished: bool,
+    steps: u32,
+    /// Each turn and the step it was taken on, for replaying the game
+    inputs: Vec<(u32, Direction)>,
+    /// Elapsed time at each tick that took a step or expired an apple. The
+    /// ticks in between change nothing that can't be caught up on later.
+    tick_times: Vec<u32>,
+    events: Vec<GameEvent>,
 }
 
 impl Game {
     pub fn new(arena_width: u32, arena_height: u32) -> Game {
+        Game::with_rules(Rules::new(arena_width, arena_height))
+    }
+
+    pub fn with_rules(rules: Rules) -> Game {
+        let arena_width = rules.arena_width;
+        let arena_height = rules.arena_height;
         let worm = {
             let x = arena_width / 2;
             let y = arena_height / 2;
@@ -37,26 +250,53 @@ impl Game {
         //let worm = (0..8).rev().map(|i| (arena_width / 2 + i, arena_height / 2)).collect();
         let mut game = Game {
             frame_time: 0,
-            step_duration: INITIAL_STEP_DURATION,
+            step_duration: rules.speed_curve.step_duration(0, 0),
+            elapsed: 0,
+            apples_eaten: 0,
+            combo: 0,
+            steps_since_meal: 0,
+            meal_start: worm[0],
+            has_eaten: false,
+            breakdown: ScoreBreakdown::default(),
             arena_width,
             arena_height,
-            rng: thread_rng(),
+            rng: match rules.seed {
+                Some(seed) => seeded_rng(seed),
+                None => thread_rng().gen(),
+            },
             worm: worm,
             direction: Direction::Right,
             new_direction: None,
             grow: false,
             apples: Vec::new(),
+            power_ups: Vec::new(),
+            active: Vec::new(),
             points: 0,
             alive: true,
+            finished: false,
+            steps: 0,
+            inputs: Vec::new(),
+            tick_times: Vec::new(),
+            events: Vec::new(),
+            rules,
         };
-        for _ in 0..1 {
+        for _ in 0..game.rules.apple_count {
             game.add_apple();
         }
         game
     }
 
     pub fn reset(&mut self) {
-        *self = Game::new(self.arena_width, self.arena_height);
+        *self = Game::with_rules(self.rules.clone());
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Starts over with new rules.
+    pub fn set_rules(&mut self, rules: Rules) {
+        *self = Game::with_rules(rules);
     }
 
     pub fn update_direction(&mut self, new_direction: Direction) {
@@ -65,24 +305,251 @@ impl Game {
         }
     }
 
+    /// Turns to the worm's left, relative to where it is going now.
+    pub fn turn_left(&mut self) {
+        let direction = self.direction.turned_left();
+        self.update_direction(direction);
+    }
+
+    /// Turns to the worm's right, relative to where it is going now.
+    pub fn turn_right(&mut self) {
+        let direction = self.direction.turned_right();
+        self.update_direction(direction);
+    }
+
+    pub fn has_power_up(&self, kind: PowerUpKind) -> bool {
+        self.active.iter().any(|active| active.kind == kind)
+    }
+
+    /// The time between steps right now, which slow motion stretches.
+    fn current_step_duration(&self) -> u32 {
+        if self.has_power_up(PowerUpKind::SlowMotion) {
+            self.step_duration * SLOW_MOTION_FACTOR
+        } else {
+            self.step_duration
+        }
+    }
+
+    /// Advances the game by `time_diff` milliseconds. Returns true if the
+    /// worm took a step or an apple went away; see `take_events` for what
+    /// happened.
+    ///
+    /// A step goes in this order: the worm moves, with the power-ups in
+    /// effect deciding what it can go through; the power-ups in effect count
+    /// down and the ones at zero end; a power-up under the head is picked
+    /// up; and last an apple under the head is eaten, scored with the
+    /// power-ups that are in effect by then.
     pub fn tick(&mut self, time_diff: u32) -> bool {
-        self.frame_time += time_diff;
-        if self.frame_time < self.step_duration {
+        if self.is_over() {
             return false;
         }
-        self.frame_time -= self.step_duration;
+        let expired = self.expire_apples(time_diff);
+        self.elapsed += time_diff;
+        if let Some(limit) = self.rules.time_limit {
+            if self.elapsed >= limit {
+                self.elapsed = limit;
+                self.finish();
+                return true;
+            }
+        }
+        self.update_speed();
+        self.frame_time += time_diff;
+        let step_duration = self.current_step_duration();
+        let stepping = self.frame_time >= step_duration;
+        if expired || stepping {
+            self.tick_times.push(self.elapsed);
+        }
+        if !stepping {
+            return expired;
+        }
+        self.frame_time -= step_duration;
         self.update_worm();
-        let current_apple = self.has_apple(self.worm[0]);
+        if !self.alive {
+            return true;
+        }
+        if self.rules.target_length.is_some_and(|length| self.worm.len() >= length) {
+            self.finish();
+            return true;
+        }
+        self.steps += 1;
+        self.steps_since_meal += 1;
+        if self.rules.grow_every.is_some_and(|steps| self.steps.is_multiple_of(steps)) {
+            self.grow = true;
+        }
+        self.update_power_ups();
+        let head = self.worm[0];
+        if let Some(i) = self.power_ups.iter().position(|power_up| power_up.pos == head) {
+            let power_up = self.power_ups.remove(i);
+            self.active.retain(|active| active.kind != power_up.kind);
+            self.active.push(ActivePowerUp { kind: power_up.kind, steps_left: power_up.kind.duration() });
+            self.events.push(GameEvent::PowerUpTaken { pos: power_up.pos, kind: power_up.kind });
+        }
+        let current_apple = self.has_apple(head);
         if let Some(i) = current_apple {
-            self.apples.remove(i);
+            let apple = self.apples.remove(i);
+            self.eat(apple);
             self.add_apple();
-            self.grow = true;
-            self.points += 5000 / self.step_duration;
-            self.step_duration = ::std::cmp::max(self.step_duration - STEP_DURATION_DECREMENT, MIN_STEP_DURATION);
+            if self.power_ups.is_empty() && Range::new(0, POWER_UP_CHANCE).ind_sample(&mut self.rng) == 0 {
+                self.add_power_up();
+            }
         }
         true
     }
 
+    /// True when the worm has died or the game has been finished.
+    pub fn is_over(&self) -> bool {
+        !self.alive || self.finished
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        self.events.push(GameEvent::Finished);
+    }
+
+    /// Milliseconds left before the time limit, if there is one.
+    pub fn time_left(&self) -> Option<u32> {
+        self.rules.time_limit.map(|limit| limit - self.elapsed)
+    }
+
+    /// Counts down both the power-ups in effect and the ones lying around.
+    fn update_power_ups(&mut self) {
+        for active in &mut self.active {
+            active.steps_left -= 1;
+            if active.steps_left == 0 {
+                self.events.push(GameEvent::PowerUpEnded { kind: active.kind });
+            }
+        }
+        self.active.retain(|active| active.steps_left > 0);
+        for power_up in &mut self.power_ups {
+            power_up.steps_left -= 1;
+            if power_up.steps_left == 0 {
+                self.events.push(GameEvent::PowerUpExpired { pos: power_up.pos });
+            }
+        }
+        self.power_ups.retain(|power_up| power_up.steps_left > 0);
+    }
+
+    /// How the points so far were earned.
+    pub fn breakdown(&self) -> &ScoreBreakdown {
+        &self.breakdown
+    }
+
+    /// Steps between two positions, going through the edges if the arena wraps.
+    fn distance(&self, a: (u32, u32), b: (u32, u32)) -> u32 {
+        let along = |a: u32, b: u32, size: u32| {
+            let d = a.abs_diff(b);
+            if self.rules.wrap { d.min(size - d) } else { d }
+        };
+        along(a.0, b.0, self.arena_width) + along(a.1, b.1, self.arena_height)
+    }
+
+    fn eat(&mut self, apple: Apple) {
+        let window = self.rules.scoring.iter().filter_map(|rule| rule.combo_window()).next();
+        let in_time = self.has_eaten && window.is_some_and(|window| self.steps_since_meal <= window);
+        self.combo = if in_time && apple.kind != AppleKind::Poison { self.combo + 1 } else { 0 };
+        let meal = Meal {
+            kind: apple.kind,
+            step_duration: self.step_duration,
+            combo: self.combo,
+            length: self.worm.len(),
+            steps: self.steps_since_meal,
+            shortest: self.distance(self.meal_start, apple.pos),
+        };
+        self.steps_since_meal = 0;
+        self.meal_start = apple.pos;
+        self.has_eaten = true;
+        let mut points = scoring::score(&self.rules.scoring, &meal, &mut self.breakdown);
+        if self.has_power_up(PowerUpKind::Multiplier) && points > 0 {
+            let extra = points * (POWER_UP_MULTIPLIER - 1);
+            self.breakdown.add("POWER-UPS", extra as i32);
+            points += extra;
+        }
+        self.points += points;
+        self.events.push(GameEvent::AppleEaten { pos: apple.pos, kind: apple.kind, points });
+        match apple.kind {
+            AppleKind::Normal | AppleKind::Golden => self.apples_eaten += 1,
+            AppleKind::Slow => self.apples_eaten = self.apples_eaten.saturating_sub(SLOW_APPLE_STEPS),
+            AppleKind::Poison => {
+                let length = self.worm.len().saturating_sub(POISON_SHRINK).max(1);
+                self.worm.truncate(length);
+                self.events.push(GameEvent::Shrank { length });
+                return;
+            },
+        }
+        self.grow = true;
+        self.update_speed();
+    }
+
+    fn update_speed(&mut self) {
+        let step_duration = self.rules.speed_curve.step_duration(self.apples_eaten, self.elapsed);
+        if step_duration != self.step_duration {
+            self.step_duration = step_duration;
+            self.events.push(GameEvent::SpeedChanged { step_duration });
+        }
+    }
+
+    /// Counts down the apples that go away and replaces the ones whose time
+    /// ran out. Returns true if any did.
+    fn expire_apples(&mut self, time_diff: u32) -> bool {
+        let mut expired = Vec::new();
+        for apple in &mut self.apples {
+            if let Some(time_left) = apple.time_left {
+                apple.time_left = Some(time_left.saturating_sub(time_diff));
+                if time_left <= time_diff {
+                    expired.push(apple.pos);
+                }
+            }
+        }
+        self.apples.retain(|apple| apple.time_left != Some(0));
+        for &pos in &expired {
+            self.events.push(GameEvent::AppleExpired { pos });
+            self.add_apple();
+        }
+        !expired.is_empty()
+    }
+
+    /// Steps the worm has taken.
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Milliseconds until the worm takes its next step, if the speed stays the same.
+    pub fn time_to_step(&self) -> u32 {
+        self.current_step_duration().saturating_sub(self.frame_time)
+    }
+
+    /// The turns taken so far, each with the number of the step it was taken on.
+    pub fn inputs(&self) -> &[(u32, Direction)] {
+        &self.inputs
+    }
+
+    /// Elapsed milliseconds at the ticks that took a step or expired an
+    /// apple. Ticking to these times plays the game out the same way.
+    pub fn tick_times(&self) -> &[u32] {
+        &self.tick_times
+    }
+
+    /// Milliseconds between steps, not counting slow motion.
+    pub fn step_duration(&self) -> u32 {
+        self.step_duration
+    }
+
+    /// Speed as a level number starting from 1, going up by one each time the
+    /// steps get faster.
+    pub fn speed_level(&self) -> u32 {
+        self.rules.speed_curve.level(self.apples_eaten, self.elapsed)
+    }
+
+    /// Milliseconds the worm has been alive.
+    pub fn elapsed(&self) -> u32 {
+        self.elapsed
+    }
+
+    /// Returns the events that happened since the previous call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        ::std::mem::take(&mut self.events)
+    }
+
     fn update_worm(&mut self) {
         if !self.alive {
             return;
@@ -90,54 +557,299 @@ impl Game {
         if let Some(direction) = self.new_direction {
             self.direction = direction;
             self.new_direction = None;
+            self.inputs.push((self.steps, direction));
+            self.events.push(GameEvent::Turned { direction });
         }
         use Direction::*;
         if self.worm.len() == 0 {
             panic!("Empty worm!");
         }
         let head = self.worm[0];
+        // Adding width - 1 is going one left, modulo the width
+        let (width, height) = (self.arena_width, self.arena_height);
         let head = match self.direction {
-            Left => (head.0 - 1, head.1),
-            Right => (head.0 + 1, head.1),
-            Up => (head.0, head.1 - 1),
-            Down => (head.0, head.1 + 1),
+            Left => ((head.0 + width - 1) % width, head.1),
+            Right => ((head.0 + 1) % width, head.1),
+            Up => (head.0, (head.1 + height - 1) % height),
+            Down => (head.0, (head.1 + 1) % height),
+        };
+        let head = if self.has_power_up(PowerUpKind::WallPhase) && !self.rules.wrap {
+            self.through_wall(head)
+        } else {
+            head
         };
-        if self.head_hits_something(head) {
+        if let Some(cause) = self.head_hits_something(head) {
             self.alive = false;
+            self.events.push(GameEvent::Died { cause });
             return;
         }
         if self.grow {
             self.worm.insert(0, head);
             self.grow = false;
+            self.events.push(GameEvent::Grew { length: self.worm.len() });
         } else {
             for i in (1..self.worm.len()).rev() {
                 self.worm[i] = self.worm[i-1];
             }
             self.worm[0] = head;
         }
+        self.events.push(GameEvent::Stepped { head });
     }
 
-    fn head_hits_something(&self, head: (u32, u32)) -> bool {
-        head.0 == 0 || head.0 == self.arena_width - 1 ||
-        head.1 == 0 || head.1 == self.arena_height - 1 ||
-        self.worm[1..].contains(&head)
+    /// Moves a position on a wall to the inside of the opposite wall.
+    fn through_wall(&self, pos: (u32, u32)) -> (u32, u32) {
+        let across = |value: u32, size: u32| {
+            if value == 0 {
+                size - 2
+            } else if value == size - 1 {
+                1
+            } else {
+                value
+            }
+        };
+        (across(pos.0, self.arena_width), across(pos.1, self.arena_height))
+    }
+
+    fn head_hits_something(&self, head: (u32, u32)) -> Option<DeathCause> {
+        let on_wall = !self.rules.wrap && (head.0 == 0 || head.0 == self.arena_width - 1 ||
+            head.1 == 0 || head.1 == self.arena_height - 1);
+        let on_obstacle = !self.has_power_up(PowerUpKind::WallPhase) && self.rules.obstacles.contains(&head);
+        if on_wall || on_obstacle {
+            Some(DeathCause::Wall)
+        } else if !self.has_power_up(PowerUpKind::Ghost) && self.worm[1..].contains(&head) {
+            Some(DeathCause::OwnBody)
+        } else {
+            None
+        }
     }
 
     fn has_apple(&self, head: (u32, u32)) -> Option<usize> {
-        self.apples.iter().position(|apple| head == *apple)
+        self.apples.iter().position(|apple| head == apple.pos)
     }
 
+    /// Adds an apple at a free spot. There is always at least one normal
+    /// apple; the others may be of any kind.
     pub fn add_apple(&mut self) {
-        let x_range = Range::new(1, self.arena_width - 1);
-        let y_range = Range::new(1, self.arena_height - 1);
+        let kind = if self.apples.iter().all(|apple| apple.kind != AppleKind::Normal) {
+            AppleKind::Normal
+        } else {
+            match Range::new(0, 10).ind_sample(&mut self.rng) {
+                0 => AppleKind::Golden,
+                1 => AppleKind::Poison,
+                2 => AppleKind::Slow,
+                _ => AppleKind::Normal,
+            }
+        };
+        let pos = self.free_spot();
+        self.apples.push(Apple::new(pos, kind));
+        self.events.push(GameEvent::AppleSpawned { pos, kind });
+    }
+
+    /// Adds a power-up of a random kind at a free spot.
+    pub fn add_power_up(&mut self) {
+        let kinds = PowerUpKind::all();
+        let kind = kinds[Range::new(0, kinds.len()).ind_sample(&mut self.rng)];
+        let pos = self.free_spot();
+        self.power_ups.push(PowerUp { pos, kind, steps_left: POWER_UP_LIFETIME });
+        self.events.push(GameEvent::PowerUpSpawned { pos, kind });
+    }
+
+    /// A random position without a wall, an obstacle, the worm, an apple or a power-up.
+    fn free_spot(&mut self) -> (u32, u32) {
+        // Without walls the whole arena is free
+        let border = if self.rules.wrap { 0 } else { 1 };
+        let x_range = Range::new(border, self.arena_width - border);
+        let y_range = Range::new(border, self.arena_height - border);
         loop {
             let x = x_range.ind_sample(&mut self.rng);
             let y = y_range.ind_sample(&mut self.rng);
             let pos = (x, y);
-            if !self.apples.contains(&pos) && !self.worm.contains(&pos) {
-                self.apples.push(pos);
-                break;
+            if !self.apples.iter().any(|apple| apple.pos == pos) && !self.power_ups.iter().any(|power_up| power_up.pos == pos)
+                && !self.worm.contains(&pos) && !self.rules.obstacles.contains(&pos) {
+                return pos;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eating_an_apple_is_reported() {
+        let mut game = Game::new(20, 15);
+        game.apples = vec![Apple::new((11, 7), AppleKind::Normal)];
+        // No new power-up comes along while there is one lying around
+        game.power_ups = vec![PowerUp { pos: (1, 1), kind: PowerUpKind::Ghost, steps_left: 100 }];
+        game.take_events();
+        assert!(game.tick(INITIAL_STEP_DURATION));
+        // Ten for the apple and one for the worm's length
+        let points = 11;
+        assert_eq!(game.take_events(), vec![
+            GameEvent::Stepped { head: (11, 7) },
+            GameEvent::AppleEaten { pos: (11, 7), kind: AppleKind::Normal, points },
+            GameEvent::SpeedChanged { step_duration: INITIAL_STEP_DURATION - STEP_DURATION_DECREMENT },
+            GameEvent::AppleSpawned { pos: game.apples[0].pos, kind: AppleKind::Normal },
+        ]);
+        assert_eq!(game.points, points);
+        assert_eq!(game.breakdown().lines, vec![("APPLES", 10), ("LENGTH", 1)]);
+        assert_eq!(game.speed_level(), 2);
+
+        assert!(!game.tick(1));
+        assert!(game.take_events().is_empty());
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.take_events()[0], GameEvent::Grew { length: 2 });
+    }
+
+    #[test]
+    fn apple_kinds() {
+        let mut rules = Rules::new(20, 15);
+        rules.apple_count = 3;
+        let mut game = Game::with_rules(rules);
+        assert_eq!(game.apples.len(), 3);
+        assert_eq!(game.apples[0].kind, AppleKind::Normal);
+
+        game.worm = vec![(10, 7), (9, 7), (8, 7), (7, 7)];
+        game.apples = vec![Apple::new((11, 7), AppleKind::Poison), Apple::new((1, 1), AppleKind::Golden)];
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.worm.len(), 2);
+        assert_eq!(game.points, 0);
+        assert_eq!(game.apples.len(), 2);
+
+        // The golden apple runs out of time and another one takes its place
+        game.apples[0] = Apple::new((1, 1), AppleKind::Golden);
+        game.take_events();
+        game.tick(GOLDEN_APPLE_LIFETIME);
+        assert!(game.take_events().contains(&GameEvent::AppleExpired { pos: (1, 1) }));
+        assert_eq!(game.apples.len(), 2);
+    }
+
+    #[test]
+    fn power_ups() {
+        let mut game = Game::new(20, 15);
+        game.apples = vec![Apple::new((1, 1), AppleKind::Normal)];
+        game.power_ups = vec![PowerUp { pos: (11, 7), kind: PowerUpKind::WallPhase, steps_left: 5 }];
+        game.tick(INITIAL_STEP_DURATION);
+        assert!(game.power_ups.is_empty());
+        assert_eq!(game.active, vec![ActivePowerUp { kind: PowerUpKind::WallPhase, steps_left: 30 }]);
+
+        // Through the right wall at x = 19 to the inside of the left one
+        for _ in 0..8 {
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert!(game.alive);
+        assert_eq!(game.worm[0], (1, 7));
+
+        game.active = vec![ActivePowerUp { kind: PowerUpKind::SlowMotion, steps_left: 1 }];
+        game.take_events();
+        assert!(!game.tick(INITIAL_STEP_DURATION));
+        assert!(game.tick(INITIAL_STEP_DURATION));
+        assert_eq!(game.take_events().last(), Some(&GameEvent::PowerUpEnded { kind: PowerUpKind::SlowMotion }));
+        assert!(game.active.is_empty());
+    }
+
+    #[test]
+    fn ghost_goes_through_the_worm() {
+        let mut game = Game::new(20, 15);
+        game.apples = vec![];
+        game.worm = vec![(10, 7), (11, 7), (11, 8), (10, 8), (9, 8), (9, 7), (9, 6)];
+        game.direction = Direction::Up;
+        game.update_direction(Direction::Left);
+        game.active = vec![ActivePowerUp { kind: PowerUpKind::Ghost, steps_left: 3 }];
+        game.tick(INITIAL_STEP_DURATION);
+        assert!(game.alive);
+        assert_eq!(game.worm[0], (9, 7));
+    }
+
+    #[test]
+    fn ending_by_the_rules() {
+        let mut rules = Rules::new(20, 15);
+        rules.time_limit = Some(1000);
+        let mut game = Game::with_rules(rules);
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.time_left(), Some(INITIAL_STEP_DURATION));
+        game.tick(INITIAL_STEP_DURATION + 1);
+        assert!(game.is_over() && game.alive);
+        assert_eq!(game.time_left(), Some(0));
+        assert_eq!(game.take_events().last(), Some(&GameEvent::Finished));
+
+        let mut rules = Rules::new(20, 15);
+        rules.apple_count = 0;
+        rules.grow_every = Some(2);
+        rules.target_length = Some(3);
+        let mut game = Game::with_rules(rules);
+        game.update_direction(Direction::Up);
+        for _ in 0..4 {
+            assert!(!game.is_over());
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert_eq!(game.worm.len(), 2);
+        game.tick(INITIAL_STEP_DURATION);
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.worm.len(), 3);
+        assert!(game.finished);
+    }
+
+    #[test]
+    fn hitting_a_wall_is_reported() {
+        let mut game = Game::new(20, 15);
+        game.apples = vec![];
+        for _ in 0..10 {
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert!(!game.alive);
+        assert_eq!(game.take_events().last(), Some(&GameEvent::Died { cause: DeathCause::Wall }));
+    }
+
+    #[test]
+    fn obstacles_and_seeds() {
+        let mut rules = Rules::new(20, 15);
+        rules.obstacles = vec![(13, 7)];
+        rules.seed = Some(20261019);
+        let mut game = Game::with_rules(rules.clone());
+        assert_eq!(Game::with_rules(rules.clone()).apples, game.apples);
+        rules.seed = Some(20261020);
+        assert_ne!(Game::with_rules(rules).apples, game.apples);
+
+        game.apples = vec![];
+        for _ in 0..3 {
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert!(!game.alive);
+        assert_eq!(game.worm[0], (12, 7));
+        assert_eq!(game.take_events().last(), Some(&GameEvent::Died { cause: DeathCause::Wall }));
+    }
+
+    #[test]
+    fn relative_turns() {
+        let mut game = Game::new(20, 15);
+        game.apples = vec![];
+        game.turn_left();
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.direction, Direction::Up);
+        game.turn_right();
+        game.turn_left();
+        game.tick(INITIAL_STEP_DURATION);
+        assert_eq!(game.direction, Direction::Right);
+        assert_eq!(game.worm[0], (11, 6));
+    }
+
+    #[test]
+    fn wrapping_around_the_edges() {
+        let mut rules = Rules::new(20, 15);
+        rules.wrap = true;
+        let mut game = Game::with_rules(rules);
+        game.apples = vec![];
+        for _ in 0..10 {
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert!(game.alive);
+        assert_eq!(game.worm[0], (0, 7));
+        game.update_direction(Direction::Up);
+        for _ in 0..8 {
+            game.tick(INITIAL_STEP_DURATION);
+        }
+        assert_eq!(game.worm[0], (0, 14));
+    }
+}
@@ -0,0 +1,227 @@
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+
+use constants::*;
+use game::Direction;
+use input::Action;
+use pointer;
+
+/// Which way a stick points, or None inside the deadzone. The axis that is
+/// further from the center wins, so a diagonal push still picks one way.
+pub fn stick_direction(x: i16, y: i16) -> Option<Direction> {
+    let (x, y) = (x as i32, y as i32);
+    if x.abs() < STICK_DEADZONE && y.abs() < STICK_DEADZONE {
+        return None;
+    }
+    Some(pointer::direction(x, y))
+}
+
+fn dpad_button(direction: Direction) -> Button {
+    match direction {
+        Direction::Up => Button::DPadUp,
+        Direction::Down => Button::DPadDown,
+        Direction::Left => Button::DPadLeft,
+        Direction::Right => Button::DPadRight,
+    }
+}
+
+/// The action a controller button does, the same as its keyboard counterpart.
+pub fn button_action(button: Button) -> Option<Action> {
+    match button {
+        Button::DPadUp => Some(Action::TurnUp),
+        Button::DPadDown => Some(Action::TurnDown),
+        Button::DPadLeft => Some(Action::TurnLeft),
+        Button::DPadRight => Some(Action::TurnRight),
+        Button::LeftShoulder => Some(Action::TurnRelativeLeft),
+        Button::RightShoulder => Some(Action::TurnRelativeRight),
+        Button::A => Some(Action::Confirm),
+        Button::B | Button::Back => Some(Action::Back),
+        Button::Start => Some(Action::Pause),
+        _ => None,
+    }
+}
+
+struct Pad {
+    controller: GameController,
+    stick: (i16, i16),
+    direction: Option<Direction>,
+}
+
+/// The connected game controllers. Controllers are opened and closed as
+/// SDL reports them being plugged in and out, which it also does for the
+/// ones connected at startup.
+pub struct Gamepads {
+    subsystem: Option<GameControllerSubsystem>,
+    pads: Vec<Pad>,
+}
+
+impl Gamepads {
+    /// Without the subsystem controllers are just ignored.
+    pub fn new(subsystem: Option<GameControllerSubsystem>) -> Gamepads {
+        Gamepads { subsystem, pads: Vec::new() }
+    }
+
+    pub fn count(&self) -> usize {
+        self.pads.len()
+    }
+
+    /// Keeps track of controllers coming and going, and turns the left stick
+    /// into D-pad presses: when the stick is pushed out of the deadzone or
+    /// swung to another direction, a `ControllerButtonDown` for that D-pad
+    /// direction comes out instead of the axis motion. Returns None for
+    /// events that are used up here.
+    pub fn translate(&mut self, event: Event) -> Option<Event> {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Some(ref subsystem) = self.subsystem {
+                    match subsystem.open(which as u32) {
+                        Ok(controller) => {
+                            // Already open ones are reported again after a remap
+                            let id = controller.instance_id();
+                            if self.pads.iter().all(|pad| pad.controller.instance_id() != id) {
+                                self.pads.push(Pad { controller, stick: (0, 0), direction: None });
+                            }
+                        },
+                        Err(e) => eprintln!("Could not open game controller {}: {}", which, e),
+                    }
+                }
+                None
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.retain(|pad| pad.controller.instance_id() != which);
+                None
+            },
+            Event::ControllerAxisMotion { timestamp, which, axis, value } => {
+                let pad = self.pads.iter_mut().find(|pad| pad.controller.instance_id() == which)?;
+                match axis {
+                    Axis::LeftX => pad.stick.0 = value,
+                    Axis::LeftY => pad.stick.1 = value,
+                    _ => return None,
+                }
+                let direction = stick_direction(pad.stick.0, pad.stick.1);
+                if direction == pad.direction {
+                    return None;
+                }
+                pad.direction = direction;
+                direction.map(|direction| Event::ControllerButtonDown { timestamp, which, button: dpad_button(direction) })
+            },
+            _ => Some(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::ptr;
+
+    use sdl2::sys::SDL_Joystick;
+
+    use super::*;
+    use input;
+
+    #[test]
+    fn deadzone_and_dominant_axis() {
+        assert_eq!(stick_direction(0, 0), None);
+        assert_eq!(stick_direction(STICK_DEADZONE as i16 - 1, -(STICK_DEADZONE as i16 - 1)), None);
+        assert_eq!(stick_direction(20000, 3000), Some(Direction::Right));
+        assert_eq!(stick_direction(-20000, 19000), Some(Direction::Left));
+        assert_eq!(stick_direction(19000, -20000), Some(Direction::Up));
+        assert_eq!(stick_direction(0, 32767), Some(Direction::Down));
+    }
+
+    const SDL_JOYSTICK_TYPE_GAMECONTROLLER: c_int = 1;
+    extern "C" {
+        fn SDL_LoadObject(sofile: *const c_char) -> *mut c_void;
+        fn SDL_LoadFunction(handle: *mut c_void, name: *const c_char) -> *mut c_void;
+        fn SDL_JoystickOpen(device_index: c_int) -> *mut SDL_Joystick;
+        fn SDL_JoystickClose(joystick: *mut SDL_Joystick);
+    }
+
+    /// The virtual joystick API is newer than the bindings (SDL 2.0.14), so it
+    /// is looked up at runtime rather than linked, which would keep all of the
+    /// tests from building against an older SDL.
+    struct VirtualJoysticks {
+        attach: AttachVirtual,
+        detach: DetachVirtual,
+        set_axis: SetVirtualAxis,
+        set_button: SetVirtualButton,
+    }
+
+    type AttachVirtual = unsafe extern "C" fn(kind: c_int, naxes: c_int, nbuttons: c_int, nhats: c_int) -> c_int;
+    type DetachVirtual = unsafe extern "C" fn(device_index: c_int) -> c_int;
+    type SetVirtualAxis = unsafe extern "C" fn(joystick: *mut SDL_Joystick, axis: c_int, value: i16) -> c_int;
+    type SetVirtualButton = unsafe extern "C" fn(joystick: *mut SDL_Joystick, button: c_int, value: u8) -> c_int;
+
+    impl VirtualJoysticks {
+        /// None if the SDL in use doesn't have them.
+        fn load() -> Option<VirtualJoysticks> {
+            unsafe {
+                // The program itself, with SDL among its libraries
+                let program = SDL_LoadObject(ptr::null());
+                if program.is_null() {
+                    return None;
+                }
+                let function = |name: &[u8]| {
+                    let function = SDL_LoadFunction(program, name.as_ptr() as *const c_char);
+                    if function.is_null() { None } else { Some(function) }
+                };
+                Some(VirtualJoysticks {
+                    attach: mem::transmute::<*mut c_void, AttachVirtual>(function(b"SDL_JoystickAttachVirtual\0")?),
+                    detach: mem::transmute::<*mut c_void, DetachVirtual>(function(b"SDL_JoystickDetachVirtual\0")?),
+                    set_axis: mem::transmute::<*mut c_void, SetVirtualAxis>(function(b"SDL_JoystickSetVirtualAxis\0")?),
+                    set_button: mem::transmute::<*mut c_void, SetVirtualButton>(function(b"SDL_JoystickSetVirtualButton\0")?),
+                })
+            }
+        }
+    }
+
+    #[test]
+    fn virtual_controller() {
+        let virtual_joysticks = match VirtualJoysticks::load() {
+            Some(virtual_joysticks) => virtual_joysticks,
+            None => {
+                eprintln!("Skipping, SDL is older than 2.0.14");
+                return;
+            },
+        };
+        let ctx = ::sdl2::init().expect("Initializing SDL 2");
+        let subsystem = ctx.game_controller().expect("Getting game controller subsystem");
+        let mut pump = ctx.event_pump().expect("Getting event pump");
+        let mut gamepads = Gamepads::new(Some(subsystem));
+        let mut actions = |gamepads: &mut Gamepads| {
+            let events: Vec<_> = pump.poll_iter().collect();
+            events.into_iter()
+                .filter_map(|event| gamepads.translate(event))
+                .filter_map(|event| input::menu_action(&event, &Default::default()))
+                .collect::<Vec<_>>()
+        };
+
+        let index = unsafe { (virtual_joysticks.attach)(SDL_JOYSTICK_TYPE_GAMECONTROLLER, 6, 15, 1) };
+        assert!(index >= 0, "Attaching a virtual joystick");
+        let joystick = unsafe { SDL_JoystickOpen(index) };
+        assert!(!joystick.is_null());
+        actions(&mut gamepads);
+        assert_eq!(gamepads.count(), 1);
+
+        // Inside the deadzone, then out to the right, then further right
+        unsafe { (virtual_joysticks.set_axis)(joystick, 0, 1000) };
+        assert_eq!(actions(&mut gamepads), vec![]);
+        unsafe { (virtual_joysticks.set_axis)(joystick, 0, 20000) };
+        assert_eq!(actions(&mut gamepads), vec![Action::TurnRight]);
+        unsafe { (virtual_joysticks.set_axis)(joystick, 0, 30000) };
+        assert_eq!(actions(&mut gamepads), vec![]);
+
+        unsafe { (virtual_joysticks.set_button)(joystick, 0, 1) };
+        assert_eq!(actions(&mut gamepads), vec![Action::Confirm]);
+
+        unsafe {
+            SDL_JoystickClose(joystick);
+            (virtual_joysticks.detach)(index);
+        }
+        actions(&mut gamepads);
+        assert_eq!(gamepads.count(), 0);
+    }
+}
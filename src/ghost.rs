@@ -0,0 +1,172 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use constants::*;
+use game::{Direction, Game, Rules};
+
+/// A run of a seeded game: its score, the turns that got it and when things
+/// happened, see `Game::tick_times`. Kept in `REPLAY_DIR`, one file per high
+/// score table, as the score on the first line, the tick times on the second
+/// and then lines of `<step> <direction>`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Replay {
+    pub score: u32,
+    pub inputs: Vec<(u32, Direction)>,
+    pub tick_times: Vec<u32>,
+}
+
+impl Replay {
+    fn path(table: &str) -> PathBuf {
+        PathBuf::from(REPLAY_DIR).join(format!("{}.replay", table))
+    }
+
+    /// None if the table has no replay, or it can't be read.
+    pub fn load(table: &str) -> Option<Replay> {
+        let mut text = String::new();
+        File::open(Replay::path(table)).and_then(|mut f| f.read_to_string(&mut text)).ok()?;
+        Replay::parse(&text)
+    }
+
+    fn parse(text: &str) -> Option<Replay> {
+        let mut lines = text.lines();
+        let score = lines.next()?.trim().parse().ok()?;
+        let tick_times = lines.next()?.split_whitespace().map(|time| time.parse().ok()).collect::<Option<_>>()?;
+        let inputs = lines.map(|line| {
+            let mut parts = line.split_whitespace();
+            let step = parts.next()?.parse().ok()?;
+            let direction = match parts.next()? {
+                "Up" => Direction::Up,
+                "Down" => Direction::Down,
+                "Left" => Direction::Left,
+                "Right" => Direction::Right,
+                _ => return None,
+            };
+            Some((step, direction))
+        }).collect::<Option<_>>()?;
+        Some(Replay { score, inputs, tick_times })
+    }
+
+    pub fn save(&self, table: &str) -> io::Result<()> {
+        fs::create_dir_all(REPLAY_DIR)?;
+        let mut f = File::create(Replay::path(table))?;
+        writeln!(f, "{}", self.score)?;
+        let tick_times: Vec<_> = self.tick_times.iter().map(|time| time.to_string()).collect();
+        writeln!(f, "{}", tick_times.join(" "))?;
+        for &(step, direction) in &self.inputs {
+            writeln!(f, "{} {:?}", step, direction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays a replay alongside the game being played, step for step, to race
+/// against.
+pub struct Ghost {
+    replay: Replay,
+    game: Game,
+    next_input: usize,
+    next_tick: usize,
+}
+
+impl Ghost {
+    /// `rules` have to be the ones the replay was recorded with, seed and all,
+    /// for the ghost to get the same apples.
+    pub fn new(rules: Rules, replay: Replay) -> Ghost {
+        Ghost { replay, game: Game::with_rules(rules), next_input: 0, next_tick: 0 }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Plays the replay until it has taken `steps` steps, or it ends. If it
+    /// is already further along, like after the game was restarted, it
+    /// starts over.
+    pub fn catch_up(&mut self, steps: u32) {
+        if self.game.steps() > steps {
+            self.game.reset();
+            self.next_input = 0;
+            self.next_tick = 0;
+        }
+        while self.game.steps() < steps && !self.game.is_over() {
+            if let Some(&(step, direction)) = self.replay.inputs.get(self.next_input) {
+                if step == self.game.steps() {
+                    self.game.new_direction = Some(direction);
+                    self.next_input += 1;
+                }
+            }
+            // Ticking straight to the recorded times expires the apples and
+            // speeds up on the same steps as in the recorded game
+            let time = match self.replay.tick_times.get(self.next_tick) {
+                Some(&time) => time.saturating_sub(self.game.elapsed()),
+                None => self.game.time_to_step().max(1),
+            };
+            self.next_tick += 1;
+            self.game.tick(time);
+            self.game.take_events();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::GameEvent;
+    use speed::SpeedCurve;
+
+    /// Ticks at about 60 frames per second until the worm has taken `steps`
+    /// steps or died. Returns whether an apple ran out of time.
+    fn play_until(game: &mut Game, steps: u32) -> bool {
+        let mut expired = false;
+        while game.steps() < steps && !game.is_over() {
+            game.tick(16 + game.elapsed() % 3 / 2);
+            expired |= game.take_events().iter().any(|event| matches!(event, GameEvent::AppleExpired { .. }));
+        }
+        expired
+    }
+
+    #[test]
+    fn replays_the_same_game() {
+        let mut rules = Rules::new(20, 15);
+        rules.seed = Some(4);
+        rules.apple_count = 6;
+        rules.wrap = true;
+        rules.speed_curve = SpeedCurve::TimeBased;
+        let mut game = Game::with_rules(rules.clone());
+        let turns = [(2, Direction::Up), (5, Direction::Left), (9, Direction::Down), (12, Direction::Right), (60, Direction::Down), (64, Direction::Left)];
+        let mut expired = false;
+        let mut played = Vec::new();
+        for step in 1..300 {
+            if let Some(&(_, direction)) = turns.iter().find(|&&(turn, _)| turn == game.steps()) {
+                game.update_direction(direction);
+            }
+            expired |= play_until(&mut game, step);
+            played.push((game.worm.clone(), game.apples.clone(), game.elapsed()));
+        }
+        // Golden apples ran out and the speed went up in between steps
+        assert!(expired);
+        assert!(game.elapsed() > TIMED_SPEED_INTERVAL * 2);
+        assert!(!game.is_over());
+        assert_eq!(game.inputs(), &turns[..]);
+
+        let replay = Replay { score: game.points, inputs: game.inputs().to_vec(), tick_times: game.tick_times().to_vec() };
+        let mut ghost = Ghost::new(rules, replay);
+        for (step, played) in (1..300).zip(played) {
+            ghost.catch_up(step);
+            assert_eq!((ghost.game().worm.clone(), ghost.game().apples.clone(), ghost.game().elapsed()), played, "step {}", step);
+        }
+        ghost.catch_up(3);
+        assert_eq!(ghost.game().steps(), 3);
+        assert_eq!(ghost.game().worm, vec![(12, 6)]);
+    }
+
+    #[test]
+    fn replay_files() {
+        let text = "7\n150 300 310\n2 Up\n5 Left\n";
+        let replay = Replay::parse(text).unwrap();
+        assert_eq!(replay, Replay { score: 7, inputs: vec![(2, Direction::Up), (5, Direction::Left)], tick_times: vec![150, 300, 310] });
+        assert_eq!(Replay::parse("7\n150\n2 Sideways\n"), None);
+        assert_eq!(Replay::parse("7\n150 soon\n"), None);
+    }
+}
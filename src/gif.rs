@@ -0,0 +1,228 @@
+#![allow(dead_code)]
+
+//! Animated GIF writer for palette images.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use sdl2::pixels::Color;
+
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    palette_bits: u8,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Writes the header, the global palette and a "loop forever" extension.
+    /// The palette can have at most 256 colors.
+    pub fn new(mut out: W, width: u16, height: u16, palette: &[Color]) -> io::Result<GifEncoder<W>> {
+        assert!(!palette.is_empty() && palette.len() <= 256);
+        let mut palette_bits = 1;
+        while 1 << palette_bits < palette.len() {
+            palette_bits += 1;
+        }
+        out.write_all(b"GIF89a")?;
+        out.write_all(&[width as u8, (width >> 8) as u8, height as u8, (height >> 8) as u8])?;
+        // Global color table present, 8 bits per primary, table size, background color 0, no aspect ratio
+        out.write_all(&[0xf0 | (palette_bits - 1), 0, 0])?;
+        for i in 0..(1 << palette_bits) {
+            let color = palette.get(i).cloned().unwrap_or(Color::RGB(0, 0, 0));
+            out.write_all(&[color.r, color.g, color.b])?;
+        }
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(GifEncoder { out, width, height, palette_bits })
+    }
+
+    /// Adds a frame of palette indices, shown for `delay` hundredths of a second.
+    pub fn add_frame(&mut self, indices: &[u8], delay: u16) -> io::Result<()> {
+        assert_eq!(indices.len(), self.width as usize * self.height as usize);
+        // Graphic control extension with the delay
+        self.out.write_all(&[0x21, 0xf9, 0x04, 0x00, delay as u8, (delay >> 8) as u8, 0x00, 0x00])?;
+        // Image descriptor covering the whole image, no local palette
+        self.out.write_all(&[0x2c, 0, 0, 0, 0,
+            self.width as u8, (self.width >> 8) as u8, self.height as u8, (self.height >> 8) as u8, 0])?;
+        let min_code_size = ::std::cmp::max(2, self.palette_bits);
+        self.out.write_all(&[min_code_size])?;
+        let data = lzw_compress(indices, min_code_size);
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+struct CodeWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.bit_buffer |= (code as u32) << self.bit_count;
+        self.bit_count += size as u32;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+}
+
+fn lzw_compress(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter { bytes: Vec::new(), bit_buffer: 0, bit_count: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end + 1;
+
+    writer.write(clear, code_size);
+    let mut prefix = match indices.first() {
+        Some(&index) => index as u16,
+        None => {
+            writer.write(end, code_size);
+            return writer.bytes;
+        }
+    };
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, code_size);
+        if next_code < 4096 {
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+            // The decoder adds its entries one code later, hence > and not >=
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end + 1;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix, code_size);
+    writer.write(end, code_size);
+    if writer.bit_count > 0 {
+        writer.bytes.push(writer.bit_buffer as u8);
+    }
+    writer.bytes
+}
+
+/// Maps RGBA pixels to indices of the closest palette colors.
+pub struct PaletteMapper {
+    palette: Vec<Color>,
+    cache: HashMap<(u8, u8, u8), u8>,
+}
+
+impl PaletteMapper {
+    pub fn new(palette: &[Color]) -> PaletteMapper {
+        PaletteMapper { palette: palette.to_vec(), cache: HashMap::new() }
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    pub fn map(&mut self, rgba: &[u8]) -> Vec<u8> {
+        let mut indices = Vec::with_capacity(rgba.len() / 4);
+        for pixel in rgba.chunks(4) {
+            let key = (pixel[0], pixel[1], pixel[2]);
+            let palette = &self.palette;
+            let index = *self.cache.entry(key).or_insert_with(|| {
+                let distance = |c: &Color| {
+                    let dr = c.r as i32 - key.0 as i32;
+                    let dg = c.g as i32 - key.1 as i32;
+                    let db = c.b as i32 - key.2 as i32;
+                    dr * dr + dg * dg + db * db
+                };
+                (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap() as u8
+            });
+            indices.push(index);
+        }
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Straightforward LZW decoder to check the encoder against.
+    fn lzw_decompress(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+        let mut bit_pos = 0;
+        let mut read = |size: u8| {
+            let mut code = 0u16;
+            for i in 0..size {
+                let bit = (data[bit_pos / 8] >> (bit_pos % 8)) & 1;
+                code |= (bit as u16) << i;
+                bit_pos += 1;
+            }
+            code
+        };
+        let mut out = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = read(code_size);
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = if (code as usize) < table.len() {
+                table[code as usize].clone()
+            } else {
+                let mut entry = previous.clone().unwrap();
+                entry.push(entry[0]);
+                entry
+            };
+            out.extend_from_slice(&entry);
+            if let Some(mut new_entry) = previous {
+                new_entry.push(entry[0]);
+                table.push(new_entry);
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        // Enough varied data to fill the code table and force a reset
+        let indices: Vec<u8> = (0..50000u32).map(|i| ((i * 7 + i / 13 + i / 1000) % 8) as u8).collect();
+        let compressed = lzw_compress(&indices, 3);
+        assert_eq!(lzw_decompress(&compressed, 3), indices);
+    }
+
+    #[test]
+    fn maps_to_closest_color() {
+        let mut mapper = PaletteMapper::new(&[Color::RGB(0, 0, 0), Color::RGB(200, 0, 0)]);
+        assert_eq!(mapper.map(&[0, 0, 0, 255, 190, 10, 0, 255, 50, 0, 0, 255]), vec![0, 1, 0]);
+    }
+}
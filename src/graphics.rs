@@ -1,54 +1,182 @@
 
-use sdl2::render::WindowCanvas;
 use sdl2::rect::Rect;
 use sdl2::pixels::Color;
 
 use constants::*;
-use text::GlyphAtlas;
-use game::Game;
+use text::{GlyphAtlas, Spacing};
+use renderer::Renderer;
+use theme::{Theme, Shape};
+use effects::Effects;
+use game::{Apple, AppleKind, Game, Direction, PowerUp};
+use input::ControlScheme;
+use mode::format_time;
 use TimeUpdate;
 //use TimeUpdate;
 
+const WORM_TILES: &[u8] = include_bytes!("../WORM.TIL");
+const WORM_TILE_COUNT: u32 = 14;
+
+// Indices into the worm tile sheet; heads and tails are in `Direction` order
+const WORM_HEAD: u8 = 0;
+const WORM_HORIZONTAL: u8 = 4;
+const WORM_VERTICAL: u8 = 5;
+const WORM_UP_RIGHT: u8 = 6;
+const WORM_RIGHT_DOWN: u8 = 7;
+const WORM_DOWN_LEFT: u8 = 8;
+const WORM_LEFT_UP: u8 = 9;
+const WORM_TAIL: u8 = 10;
+
+/// The worm tile sheet: heads facing each direction, straight and corner
+/// body pieces, and tails, one `BLOCK_SIZE` square per tile.
+pub fn worm_tiles() -> GlyphAtlas {
+    GlyphAtlas::from_bitmap(WORM_TILES, 16, WORM_TILE_COUNT)
+}
+
 fn rect(x: u32, y: u32, w: u32, h: u32) -> Rect {
     Rect::new(x as i32, y as i32, w, h)
 }
 
-pub fn draw_start_screen(canvas: &mut WindowCanvas, atlas: &mut GlyphAtlas, time_update: TimeUpdate) {
-    setup_full_viewport(canvas);
-    let position = (WINDOW_WIDTH as i32 / 2, WINDOW_HEIGHT as i32 / 2);
+/// The animated title with the start menu below it.
+pub fn draw_start_screen<R: Renderer, S: AsRef<str>>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, time_update: TimeUpdate, labels: &[S], selected: usize) {
+    setup_full_viewport(renderer);
+    let position = (WINDOW_WIDTH as i32 / 2, MENU_TITLE_Y);
     // Hacky way to avoid bringing in arrayvec
     let mut chars: [_; 4] = [(0, (Rect::new(0, 0, 0, 0), Rect::new(0, 0, 0, 0))); 4];
-    for item in atlas.text_rects_centered("Mato", position, 6).enumerate() {
+    for item in atlas.text_rects_centered("Mato", position, 4).enumerate() {
         chars[item.0] = item;
     }
     for &(i, (src, dst)) in &chars[..] {
-        if (time_update.absolute / START_SCREEN_SPEED) % 4 == i as u32 {
+        let color = if (time_update.absolute / START_SCREEN_SPEED) % 4 == i as u32 {
         //if (start_counter >> i) & 1 == 1 {
-            atlas.set_render_color(TITLE_COLOR_2.0, TITLE_COLOR_2.1, TITLE_COLOR_2.2);
+            theme.title_2
         } else {
-            atlas.set_render_color(TITLE_COLOR_1.0, TITLE_COLOR_1.1, TITLE_COLOR_1.2);
+            theme.title_1
+        };
+        renderer.draw_glyphs(atlas, &mut Some((src, dst)).into_iter(), color);
+    }
+    draw_menu_items(renderer, atlas, theme, labels, selected);
+}
+
+/// A screen with a title and a list of items, one of them highlighted.
+pub fn draw_menu<R: Renderer, S: AsRef<str>>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, title: &str, labels: &[S], selected: usize) {
+    setup_full_viewport(renderer);
+    let position = (WINDOW_WIDTH as i32 / 2, MENU_TITLE_Y);
+    renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(title, position, 2), theme.title_2);
+    draw_menu_items(renderer, atlas, theme, labels, selected);
+}
+
+/// The first item shown and how many fit. Menus longer than the screen
+/// scroll to keep the selected item in view.
+fn visible_menu_items(count: usize, selected: usize) -> (usize, usize) {
+    let visible = ((WINDOW_HEIGHT as i32 - MENU_TOP - MENU_LINE_HEIGHT / 2) / MENU_LINE_HEIGHT + 1) as usize;
+    let first = selected.saturating_sub(visible / 2).min(count.saturating_sub(visible));
+    (first, visible)
+}
+
+/// The index of the menu item drawn on the row at logical `y`, if any.
+pub fn menu_item_at(y: i32, count: usize, selected: usize) -> Option<usize> {
+    let (first, visible) = visible_menu_items(count, selected);
+    let top = MENU_TOP - MENU_LINE_HEIGHT / 2;
+    if y < top {
+        return None;
+    }
+    let row = ((y - top) / MENU_LINE_HEIGHT) as usize;
+    if row < visible && first + row < count { Some(first + row) } else { None }
+}
+
+fn draw_menu_items<R: Renderer, S: AsRef<str>>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, labels: &[S], selected: usize) {
+    let (first, visible) = visible_menu_items(labels.len(), selected);
+    for (i, label) in labels.iter().enumerate().skip(first).take(visible) {
+        let position = (WINDOW_WIDTH as i32 / 2, MENU_TOP + (i - first) as i32 * MENU_LINE_HEIGHT);
+        if i == selected {
+            let text = format!("> {} <", label.as_ref());
+            renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(&text[..], position, 1), theme.title_2);
+        } else {
+            renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(label.as_ref(), position, 1), theme.title_1);
         }
-        canvas.copy(&atlas.texture(), src, dst).expect("Could not draw glyph");
     }
 }
 
-pub fn draw_game(canvas: &mut WindowCanvas, game: &Game, atlas: &mut GlyphAtlas) {
-    setup_status_bar_viewport(canvas);
-    draw_points(canvas, atlas, game.points);
+/// A title and lines of text, for screens that are just read.
+pub fn draw_text_screen<R: Renderer, S: AsRef<str>>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, title: &str, lines: &[S]) {
+    setup_full_viewport(renderer);
+    let position = (WINDOW_WIDTH as i32 / 2, MENU_TITLE_Y);
+    renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(title, position, 2), theme.title_2);
+    for (i, line) in lines.iter().enumerate() {
+        if line.as_ref().is_empty() {
+            continue;
+        }
+        let position = (WINDOW_WIDTH as i32 / 2, MENU_TOP + i as i32 * TEXT_LINE_HEIGHT);
+        renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(line.as_ref(), position, 1), theme.title_1);
+    }
+}
 
-    setup_game_viewport(canvas);
-    draw_arena(canvas);
-    draw_apples(canvas, &game.apples);
-    draw_worm(canvas, &game.worm);
+/// What the status bar shows besides the game's own state.
+pub struct Hud<'a> {
+    pub mode: &'a str,
+    /// Already formatted for the mode, since it may be points or a time.
+    pub high_score: &'a str,
+    pub control_scheme: ControlScheme,
+    /// Points ahead of the ghost, or behind it if negative, when racing one.
+    pub ghost_lead: Option<i32>,
 }
 
-pub fn draw_game_over(canvas: &mut WindowCanvas, game: &Game, atlas: &mut GlyphAtlas, time_update: TimeUpdate) {
-    setup_status_bar_viewport(canvas);
-    draw_points(canvas, atlas, game.points);
+/// Mode, high score, worm length, speed, time and control scheme on the left; points on the right.
+/// The time counts down when there is a time limit, and shows milliseconds when racing to a length.
+/// Below them the power-ups in effect, with the steps they have left, and how the race
+/// against the ghost is going.
+pub fn draw_status_bar<R: Renderer>(renderer: &mut R, game: &Game, hud: &Hud, atlas: &GlyphAtlas, theme: &Theme) {
+    setup_status_bar_viewport(renderer);
+    draw_points(renderer, atlas, theme, game.points);
+
+    let row_1 = 2;
+    let row_2 = row_1 + atlas.glyph_height() as i32;
+    let column_1 = 4;
+    let column_2 = WINDOW_WIDTH as i32 * 2 / 5;
+    let clock = if game.rules().target_length.is_some() {
+        format_time(game.elapsed())
+    } else {
+        // Rounded up when counting down, so that 0:00 is when time is up
+        let seconds = game.time_left().map_or(game.elapsed() / 1000, |left| left.div_ceil(1000));
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    };
+    let texts = [
+        (hud.mode.to_uppercase(), (column_1, row_1)),
+        (format!("LEN {}  SPD {}", game.worm.len(), game.speed_level()), (column_1, row_2)),
+        (format!("HI {}", hud.high_score), (column_2, row_1)),
+        (format!("{}  {}", clock, hud.control_scheme.tag()), (column_2, row_2)),
+    ];
+    for &(ref text, position) in &texts {
+        renderer.draw_glyphs(atlas, &mut atlas.text_rects(&text[..], position, 1), theme.title_1);
+    }
+    let power_ups: Vec<_> = game.active.iter().map(|active| format!("{} {}", active.kind.tag(), active.steps_left)).collect();
+    let row_3 = row_2 + atlas.glyph_height() as i32;
+    renderer.draw_glyphs(atlas, &mut atlas.text_rects(&power_ups.join("  ")[..], (column_1, row_3), 1), theme.title_2);
+    if let Some(lead) = hud.ghost_lead {
+        let text = format!("GHOST {:+}", lead);
+        renderer.draw_glyphs(atlas, &mut atlas.text_rects_right_aligned(&text[..], (WINDOW_WIDTH as i32 - 4, row_3), 1), theme.title_1);
+    }
+}
 
-    setup_game_viewport(canvas);
-    draw_arena(canvas);
-    draw_apples(canvas, &game.apples);
+/// `ghost` is a replayed game to race against; only its worm is drawn, see-through.
+pub fn draw_game<R: Renderer>(renderer: &mut R, game: &Game, ghost: Option<&Game>, atlas: &GlyphAtlas, tiles: &GlyphAtlas, theme: &Theme, effects: &Effects) {
+    setup_game_viewport(renderer, game, effects.shake_offset());
+    draw_arena(renderer, game, theme);
+    draw_apples(renderer, theme, &game.apples);
+    draw_power_ups(renderer, atlas, theme, &game.power_ups);
+    if let Some(ghost) = ghost {
+        let color = Color::RGBA(theme.worm.r, theme.worm.g, theme.worm.b, GHOST_ALPHA);
+        draw_worm(renderer, tiles, theme, &ghost.worm, ghost.direction, color);
+    }
+    draw_worm(renderer, tiles, theme, &game.worm, game.direction, theme.worm);
+    effects.draw(renderer, atlas, theme);
+}
+
+pub fn draw_game_over<R: Renderer>(renderer: &mut R, game: &Game, atlas: &GlyphAtlas, tiles: &GlyphAtlas, theme: &Theme, effects: &Effects, time_update: TimeUpdate) {
+    setup_game_viewport(renderer, game, effects.shake_offset());
+    draw_arena(renderer, game, theme);
+    draw_apples(renderer, theme, &game.apples);
+    draw_power_ups(renderer, atlas, theme, &game.power_ups);
     //draw_worm(canvas, &game.worm);
 
     let dead_segment = if time_update.absolute > GAME_OVER_SCREEN_DELAY {
@@ -57,34 +185,45 @@ pub fn draw_game_over(canvas: &mut WindowCanvas, game: &Game, atlas: &mut GlyphA
         0
     };
     for (i, segment) in game.worm.iter().enumerate() {
-        if dead_segment > i as u32 {
+        if !game.finished && dead_segment > i as u32 {
 
             //let r = rect(x, y, size, size);
             //canvas.set_draw_color(WORM_COLOR);
             //canvas.fill_rect(r).expect("Drawing worm");
 
-            canvas.set_draw_color(Color { r: 255, g: 255, b: 255, a: 255 });
             let size = BLOCK_SIZE / 2;
             let offset = BLOCK_SIZE / 4;
             let x = segment.0 * BLOCK_SIZE + offset;
             let y = segment.1 * BLOCK_SIZE + offset;
             let r = rect(x, y, size, size);
-            canvas.fill_rect(r).expect("Drawing worm");
+            renderer.fill_rect(r, theme.dead_worm);
         } else {
-            canvas.set_draw_color(WORM_COLOR);
-            let x = segment.0 * BLOCK_SIZE + 1;
-            let y = segment.1 * BLOCK_SIZE + 1;
-            let size = BLOCK_SIZE - 2;
-            let r = rect(x, y, size, size);
-            canvas.fill_rect(r).expect("Drawing worm");
+            draw_worm_segment(renderer, tiles, theme, &game.worm, i, game.direction, theme.worm);
         };
     }
 
-    setup_full_viewport(canvas);
-    let bg = (BACKGROUND_COLOR.r, BACKGROUND_COLOR.g, BACKGROUND_COLOR.b);
-    let fg = TITLE_COLOR_2;
-    let game_over_scale = 3;
-    // Draw the text multiple times with black to give borders to it
+    effects.draw(renderer, atlas, theme);
+
+    setup_full_viewport(renderer);
+    let position = (WINDOW_WIDTH as i32 / 2, WINDOW_HEIGHT as i32 / 2);
+    let title = match game.time_left() {
+        _ if !game.finished => "GAME OVER",
+        Some(_) => "TIME UP",
+        None => "FINISHED",
+    };
+    draw_outlined_text(renderer, atlas, title, position, 3, theme.title_2, theme.background);
+}
+
+/// Goes on top of `draw_game`.
+pub fn draw_paused<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme) {
+    setup_full_viewport(renderer);
+    let position = (WINDOW_WIDTH as i32 / 2, WINDOW_HEIGHT as i32 / 2);
+    draw_outlined_text(renderer, atlas, "PAUSED", position, 3, theme.title_2, theme.background);
+}
+
+/// Centered text with a border around it, so that it can be read on top of the game.
+fn draw_outlined_text<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, text: &str, position: (i32, i32), scale: u32, fg: Color, bg: Color) {
+    // Draw the text multiple times with the background color to give borders to it
     let text_repetitions = [
         (-1, -1, bg),
         (0, -1, bg),
@@ -100,33 +239,64 @@ pub fn draw_game_over(canvas: &mut WindowCanvas, game: &Game, atlas: &mut GlyphA
         (0, 0, fg)
     ];
     for &(x, y, color) in &text_repetitions {
-        let x = x * game_over_scale;
-        let y = y * game_over_scale;
-        let position = (WINDOW_WIDTH as i32 / 2 + x, WINDOW_HEIGHT as i32 / 2 + y);
-        atlas.set_render_color(color.0, color.1, color.2);
-        for (src, dst) in atlas.text_rects_centered("GAME OVER", position, game_over_scale as u32) {
-            canvas.copy(&atlas.texture(), src, dst).expect("Could not draw glyph");
-        }
+        let x = x * scale as i32;
+        let y = y * scale as i32;
+        let position = (position.0 + x, position.1 + y);
+        renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(text, position, scale), color);
     }
-    //let position = (WINDOW_WIDTH as i32 / 2, WINDOW_HEIGHT as i32 / 2);
-    //for (src, dst) in atlas.text_rects_centered("GAME OVER", position, 3) {
-    //    canvas.copy(&atlas.texture(), src, dst).expect("Could not draw glyph");
-    //}
 }
 
-fn setup_full_viewport(canvas: &mut WindowCanvas) {
-    canvas.set_viewport(rect(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT));
+/// Short message in the left end of the status bar, drawn on top of whatever screen is showing.
+pub fn draw_toast<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, text: &str) {
+    setup_status_bar_viewport(renderer);
+    let glyphs: Vec<_> = atlas.text_rects(text, (0, 0), 2).collect();
+    // Blank out whatever the status bar has under the message
+    let width = glyphs.last().map_or(0, |&(_, dst)| dst.right() as u32);
+    renderer.fill_rect(rect(0, 0, width + 4, STATUS_BAR_HEIGHT), theme.background);
+    renderer.draw_glyphs(atlas, &mut glyphs.into_iter(), theme.title_2);
+}
+
+/// Speed curve and step duration in the bottom left corner, for tuning the curves.
+pub fn draw_debug_overlay<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, game: &Game) {
+    setup_full_viewport(renderer);
+    let text = format!("{} {}MS", game.rules().speed_curve.name().to_uppercase(), game.step_duration());
+    let glyphs: Vec<_> = atlas.text_rects(&text[..], (2, WINDOW_HEIGHT as i32 - atlas.glyph_height() as i32 - 2), 1).collect();
+    let width = glyphs.last().map_or(0, |&(_, dst)| dst.right() as u32);
+    renderer.fill_rect(rect(0, WINDOW_HEIGHT - atlas.glyph_height() - 4, width + 2, atlas.glyph_height() + 4), theme.background);
+    renderer.draw_glyphs(atlas, &mut glyphs.into_iter(), theme.points);
 }
 
-fn setup_game_viewport(canvas: &mut WindowCanvas) {
-    canvas.set_viewport(rect(0, STATUS_BAR_HEIGHT, ARENA_WIDTH_PX, ARENA_HEIGHT_PX));
+fn setup_full_viewport<R: Renderer>(renderer: &mut R) {
+    renderer.set_viewport(rect(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT));
 }
 
-fn setup_status_bar_viewport(canvas: &mut WindowCanvas) {
-    canvas.set_viewport(rect(0, 0, WINDOW_WIDTH, STATUS_BAR_HEIGHT));
+/// Logical coordinates of the arena's top left corner. Smaller arenas are centered.
+fn arena_origin(game: &Game) -> (i32, i32) {
+    let width = game.arena_width * BLOCK_SIZE;
+    let height = game.arena_height * BLOCK_SIZE;
+    ((ARENA_WIDTH_PX - width) as i32 / 2, STATUS_BAR_HEIGHT as i32 + (ARENA_HEIGHT_PX - height) as i32 / 2)
 }
 
-fn draw_points(canvas: &mut WindowCanvas, atlas: &mut GlyphAtlas, points: u32) {
+/// Logical coordinates of the center of the worm's head.
+pub fn worm_head_center(game: &Game) -> (i32, i32) {
+    let (x, y) = arena_origin(game);
+    let head = game.worm[0];
+    let half = BLOCK_SIZE as i32 / 2;
+    (x + (head.0 * BLOCK_SIZE) as i32 + half, y + (head.1 * BLOCK_SIZE) as i32 + half)
+}
+
+/// Arenas smaller than the window are centered in the space below the status
+/// bar. `offset` moves the arena around for screen shake.
+fn setup_game_viewport<R: Renderer>(renderer: &mut R, game: &Game, offset: (i32, i32)) {
+    let (x, y) = arena_origin(game);
+    renderer.set_viewport(Rect::new(x + offset.0, y + offset.1, game.arena_width * BLOCK_SIZE, game.arena_height * BLOCK_SIZE));
+}
+
+fn setup_status_bar_viewport<R: Renderer>(renderer: &mut R) {
+    renderer.set_viewport(rect(0, 0, WINDOW_WIDTH, STATUS_BAR_HEIGHT));
+}
+
+fn draw_points<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, points: u32) {
     let digits = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
     let mut text_bytes = [' ' as u8; 10];
     let text_bytes_len = text_bytes.len();
@@ -140,32 +310,320 @@ fn draw_points(canvas: &mut WindowCanvas, atlas: &mut GlyphAtlas, points: u32) {
     //let position = (WINDOW_WIDTH as i32 / 2, WINDOW_HEIGHT as i32 / 2);
     let is_nonzero_digit = |c: &u8| *c != ' ' as u8 && *c != '0' as u8;
     let start = text_bytes.iter().position(is_nonzero_digit).unwrap_or(text_bytes.len() - 1);
-    atlas.set_render_color(POINTS_COLOR.0, POINTS_COLOR.1, POINTS_COLOR.2);
-    for (src, dst) in atlas.text_rects_right_aligned(&text_bytes[start..], (WINDOW_WIDTH as i32, 0), 2) {
-        canvas.copy(&atlas.texture(), src, dst).expect("Could not draw glyph");
+    // In whole cells, so that the digits don't shift about as the points go up
+    let width = atlas.text_width(&text_bytes[start..], 2, Spacing::Monospace);
+    let mut glyphs = atlas.text_rects(&text_bytes[start..], (WINDOW_WIDTH as i32 - width, 0), 2).monospace();
+    renderer.draw_glyphs(atlas, &mut glyphs, theme.points);
+}
+
+/// Walls around the arena, or with wrapping just a thin line to show where the edges are.
+fn draw_arena<R: Renderer>(renderer: &mut R, game: &Game, theme: &Theme) {
+    let width = game.arena_width * BLOCK_SIZE;
+    let height = game.arena_height * BLOCK_SIZE;
+    let thickness = if game.rules().wrap { 1 } else { BLOCK_SIZE };
+    renderer.fill_rects(&[
+        rect(0, 0, width, thickness),
+        rect(0, height - thickness, width, thickness),
+        rect(0, 0, thickness, height),
+        rect(width - thickness, 0, thickness, height),
+    ], theme.wall);
+    let obstacles: Vec<_> = game.rules().obstacles.iter()
+        .map(|&(x, y)| rect(x * BLOCK_SIZE, y * BLOCK_SIZE, BLOCK_SIZE, BLOCK_SIZE))
+        .collect();
+    renderer.fill_rects(&obstacles, theme.wall);
+}
+
+/// Each kind has its own color and, for telling them apart without colors,
+/// its own mark: poison apples are hollow, slow ones have a bar across, and
+/// golden ones blink when they are about to go away.
+fn draw_apples<R: Renderer>(renderer: &mut R, theme: &Theme, apples: &[Apple]) {
+    for apple in apples {
+        let color = match apple.kind {
+            AppleKind::Normal => theme.apple,
+            AppleKind::Golden => theme.golden_apple,
+            AppleKind::Poison => theme.poison_apple,
+            AppleKind::Slow => theme.slow_apple,
+        };
+        if let Some(time_left) = apple.time_left {
+            if time_left < GOLDEN_APPLE_WARNING && (time_left / 125) % 2 == 0 {
+                continue;
+            }
+        }
+        draw_block(renderer, theme.apple_shape, apple.pos, color);
+        let x = apple.pos.0 * BLOCK_SIZE;
+        let y = apple.pos.1 * BLOCK_SIZE;
+        match apple.kind {
+            AppleKind::Poison => draw_shape(renderer, theme.apple_shape, x + 5, y + 5, BLOCK_SIZE - 10, theme.background),
+            AppleKind::Slow => renderer.fill_rect(rect(x + 4, y + BLOCK_SIZE / 2 - 1, BLOCK_SIZE - 8, 2), theme.background),
+            _ => {},
+        }
     }
 }
 
-fn draw_arena(canvas: &mut WindowCanvas) {
-    canvas.set_draw_color(WALL_COLOR);
-    canvas.fill_rects(&[
-        rect(0, 0, ARENA_WIDTH_PX, BLOCK_SIZE),
-        rect(0, ARENA_HEIGHT_PX - BLOCK_SIZE, ARENA_WIDTH_PX, BLOCK_SIZE),
-        rect(0, 0, BLOCK_SIZE, ARENA_HEIGHT_PX),
-        rect(ARENA_WIDTH_PX - BLOCK_SIZE, 0, BLOCK_SIZE, ARENA_HEIGHT_PX),
-    ]).expect("Drawing walls");
+/// A block with the first letter of the power-up cut out of it.
+fn draw_power_ups<R: Renderer>(renderer: &mut R, atlas: &GlyphAtlas, theme: &Theme, power_ups: &[PowerUp]) {
+    for power_up in power_ups {
+        draw_block(renderer, Shape::Square, power_up.pos, theme.points);
+        let center = ((power_up.pos.0 * BLOCK_SIZE + BLOCK_SIZE / 2) as i32, (power_up.pos.1 * BLOCK_SIZE + BLOCK_SIZE / 2) as i32);
+        renderer.draw_glyphs(atlas, &mut atlas.text_rects_centered(&power_up.kind.tag()[..1], center, 1), theme.background);
+    }
 }
 
-fn draw_apples(canvas: &mut WindowCanvas, apples: &[(u32, u32)]) {
-    canvas.set_draw_color(APPLE_COLOR);
-    for pos in apples.iter() {
-        canvas.fill_rect(rect(pos.0 * BLOCK_SIZE + 1, pos.1 * BLOCK_SIZE + 1, BLOCK_SIZE - 2, BLOCK_SIZE - 2)).expect("Drawing apple");
+fn draw_worm<R: Renderer>(renderer: &mut R, tiles: &GlyphAtlas, theme: &Theme, worm: &[(u32, u32)], direction: Direction, color: Color) {
+    for i in 0..worm.len() {
+        draw_worm_segment(renderer, tiles, theme, worm, i, direction, color);
     }
 }
 
-fn draw_worm(canvas: &mut WindowCanvas, worm: &Vec<(u32, u32)>) {
-    canvas.set_draw_color(WORM_COLOR);
-    for segment in worm.iter() {
-        canvas.fill_rect(rect(segment.0 * BLOCK_SIZE + 1, segment.1 * BLOCK_SIZE + 1, BLOCK_SIZE - 2, BLOCK_SIZE - 2)).expect("Drawing worm");
+fn draw_worm_segment<R: Renderer>(renderer: &mut R, tiles: &GlyphAtlas, theme: &Theme, worm: &[(u32, u32)], i: usize, direction: Direction, color: Color) {
+    if theme.worm_shape != Shape::Sprites {
+        draw_block(renderer, theme.worm_shape, worm[i], color);
+        return;
+    }
+    let src = tiles.glyph_rect(worm_tile(worm, i, direction));
+    let dst = rect(worm[i].0 * BLOCK_SIZE, worm[i].1 * BLOCK_SIZE, BLOCK_SIZE, BLOCK_SIZE);
+    renderer.draw_glyphs(tiles, &mut Some((src, dst)).into_iter(), color);
+}
+
+/// Picks the tile for the i'th segment from the segments next to it.
+/// `direction` is only needed for a worm that has nothing but a head.
+fn worm_tile(worm: &[(u32, u32)], i: usize, direction: Direction) -> u8 {
+    use Direction::*;
+    let index = |direction: Direction| match direction {
+        Up => 0,
+        Down => 1,
+        Left => 2,
+        Right => 3,
+    };
+    if worm.len() == 1 {
+        return WORM_HEAD + index(direction);
+    }
+    if i == 0 {
+        let facing = match side(worm[0], worm[1]) {
+            Up => Down,
+            Down => Up,
+            Left => Right,
+            Right => Left,
+        };
+        return WORM_HEAD + index(facing);
+    }
+    let towards_head = side(worm[i], worm[i - 1]);
+    if i == worm.len() - 1 {
+        return WORM_TAIL + index(towards_head);
+    }
+    match (towards_head, side(worm[i], worm[i + 1])) {
+        (Up, Right) | (Right, Up) => WORM_UP_RIGHT,
+        (Right, Down) | (Down, Right) => WORM_RIGHT_DOWN,
+        (Down, Left) | (Left, Down) => WORM_DOWN_LEFT,
+        (Left, Up) | (Up, Left) => WORM_LEFT_UP,
+        (Up, _) | (Down, _) => WORM_VERTICAL,
+        _ => WORM_HORIZONTAL,
+    }
+}
+
+/// Which side of `from` the neighbouring block `to` is on.
+/// Segments further apart than one block are joined through the edge of the
+/// arena, after wrapping around or phasing through a wall, so they lie the other way.
+fn side(from: (u32, u32), to: (u32, u32)) -> Direction {
+    let through_edge = |a: u32, b: u32| (a as i32 - b as i32).abs() > 1;
+    if to.0 != from.0 && (to.0 < from.0) != through_edge(to.0, from.0) {
+        Direction::Left
+    } else if to.0 != from.0 {
+        Direction::Right
+    } else if (to.1 < from.1) != through_edge(to.1, from.1) {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+/// Fills the arena block at `pos` with the shape, leaving a one pixel gap around it.
+fn draw_block<R: Renderer>(renderer: &mut R, shape: Shape, pos: (u32, u32), color: Color) {
+    draw_shape(renderer, shape, pos.0 * BLOCK_SIZE + 1, pos.1 * BLOCK_SIZE + 1, BLOCK_SIZE - 2, color);
+}
+
+fn draw_shape<R: Renderer>(renderer: &mut R, shape: Shape, x: u32, y: u32, size: u32, color: Color) {
+    if shape == Shape::Square || shape == Shape::Sprites {
+        renderer.fill_rect(rect(x, y, size, size), color);
+        return;
+    }
+    // Other shapes are drawn as one horizontal span per row
+    let radius = size as f32 / 2.0;
+    for row in 0..size {
+        let dy = row as f32 + 0.5 - radius;
+        let half_width = match shape {
+            Shape::Circle => (radius * radius - dy * dy).sqrt(),
+            _ => radius - dy.abs(),
+        };
+        let half_width = half_width.round() as u32;
+        if half_width > 0 {
+            renderer.fill_rect(rect(x + size / 2 - half_width, y + row, half_width * 2, 1), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+
+    use super::*;
+    use game::{ActivePowerUp, Direction, PowerUpKind, Rules};
+    use renderer::Framebuffer;
+    use png;
+
+    /// Renders a screen into a framebuffer the size of the window and compares
+    /// it to `tests/golden/<name>.png`. Run the tests with `MATO_BLESS=1` to
+    /// (re)write the references after an intended change to the graphics.
+    fn check_golden<F: Fn(&mut Framebuffer, &GlyphAtlas, &Theme)>(name: &str, draw: F) {
+        check_golden_with_theme(name, &Theme::classic(), draw);
+    }
+
+    fn check_golden_with_theme<F: Fn(&mut Framebuffer, &GlyphAtlas, &Theme)>(name: &str, theme: &Theme, draw: F) {
+        let atlas = GlyphAtlas::new();
+        let mut framebuffer = Framebuffer::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+        framebuffer.clear(theme.background);
+        draw(&mut framebuffer, &atlas, theme);
+        framebuffer.present();
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests");
+        path.push("golden");
+        path.push(format!("{}.png", name));
+        let encoded = png::encode(framebuffer.width(), framebuffer.height(), framebuffer.pixels());
+
+        if env::var_os("MATO_BLESS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).expect("Creating golden image directory");
+            File::create(&path).and_then(|mut f| f.write_all(&encoded)).expect("Writing golden image");
+            return;
+        }
+
+        let mut reference = Vec::new();
+        File::open(&path).and_then(|mut f| f.read_to_end(&mut reference))
+            .unwrap_or_else(|e| panic!("Reading {}: {} (run with MATO_BLESS=1 to create it)", path.display(), e));
+        let reference = png::decode(&reference).unwrap_or_else(|e| panic!("Decoding {}: {}", path.display(), e));
+        assert_eq!((reference.width, reference.height), (framebuffer.width(), framebuffer.height()),
+            "{} has the wrong size", path.display());
+        let differing = reference.pixels.chunks(4).zip(framebuffer.pixels().chunks(4)).filter(|&(a, b)| a != b).count();
+        if differing > 0 {
+            let mut actual = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            actual.push("target");
+            actual.push(format!("{}.actual.png", name));
+            File::create(&actual).and_then(|mut f| f.write_all(&encoded)).expect("Writing actual image");
+            panic!("{} pixels differ from {}, see {}", differing, path.display(), actual.display());
+        }
+    }
+
+    fn time(absolute: u32) -> TimeUpdate {
+        TimeUpdate { absolute, diff: 0 }
+    }
+
+    fn sample_game() -> Game {
+        let mut game = Game::new(ARENA_WIDTH_BLOCKS, ARENA_HEIGHT_BLOCKS);
+        game.worm = vec![(8, 5), (7, 5), (6, 5), (6, 6), (6, 7), (7, 7)];
+        game.direction = Direction::Right;
+        game.apples = vec![
+            Apple::new((12, 9), AppleKind::Normal),
+            Apple::new((3, 3), AppleKind::Golden),
+            Apple::new((5, 3), AppleKind::Poison),
+            Apple::new((7, 3), AppleKind::Slow),
+        ];
+        game.power_ups = vec![PowerUp { pos: (14, 3), kind: PowerUpKind::Ghost, steps_left: 10 }];
+        game.active = vec![ActivePowerUp { kind: PowerUpKind::Multiplier, steps_left: 12 }];
+        game.points = 1234;
+        game
+    }
+
+    fn menu_labels() -> Vec<&'static str> {
+        vec!["NEW GAME", "OPTIONS", "QUIT"]
+    }
+
+    fn sample_hud() -> Hud<'static> {
+        Hud { mode: "Classic", high_score: "5678", control_scheme: ControlScheme::Relative, ghost_lead: None }
+    }
+
+    #[test]
+    fn start_screen() {
+        check_golden("start_0", |fb, atlas, theme| draw_start_screen(fb, atlas, theme, time(0), &menu_labels(), 0));
+        check_golden("start_1", |fb, atlas, theme| draw_start_screen(fb, atlas, theme, time(START_SCREEN_SPEED), &menu_labels(), 1));
+    }
+
+    #[test]
+    fn menu_screen() {
+        check_golden("menu", |fb, atlas, theme| draw_menu(fb, atlas, theme, "OPTIONS", &menu_labels(), 2));
+    }
+
+    #[test]
+    fn clicking_menu_items() {
+        assert_eq!(menu_item_at(MENU_TOP, 3, 0), Some(0));
+        assert_eq!(menu_item_at(MENU_TOP + MENU_LINE_HEIGHT * 2, 3, 0), Some(2));
+        assert_eq!(menu_item_at(MENU_TOP + MENU_LINE_HEIGHT * 3, 3, 0), None);
+        assert_eq!(menu_item_at(MENU_TITLE_Y, 3, 0), None);
+        // Scrolled down so that the last of 20 items is at the bottom
+        let (first, visible) = visible_menu_items(20, 19);
+        assert_eq!(first + visible, 20);
+        assert_eq!(menu_item_at(MENU_TOP, 20, 19), Some(first));
+    }
+
+    #[test]
+    fn worm_tiles_across_edges() {
+        // Turned right from going up and wrapped around the right edge
+        let worm = [(0, 5), (19, 5), (19, 6)];
+        assert_eq!(worm_tile(&worm, 0, Direction::Right), WORM_HEAD + 3);
+        assert_eq!(worm_tile(&worm, 1, Direction::Right), WORM_RIGHT_DOWN);
+        assert_eq!(worm_tile(&worm, 2, Direction::Right), WORM_TAIL);
+        // Phased through the bottom wall
+        let worm = [(4, 1), (4, 13), (4, 12)];
+        assert_eq!(worm_tile(&worm, 0, Direction::Down), WORM_HEAD + 1);
+        assert_eq!(worm_tile(&worm, 1, Direction::Down), WORM_VERTICAL);
+        assert_eq!(worm_tile(&worm, 2, Direction::Down), WORM_TAIL + 1);
+    }
+
+    #[test]
+    fn game_screen() {
+        let game = sample_game();
+        check_golden("game", |fb, atlas, theme| {
+            draw_status_bar(fb, &game, &sample_hud(), atlas, theme);
+            draw_game(fb, &game, None, atlas, &worm_tiles(), theme, &Effects::new());
+        });
+    }
+
+    #[test]
+    fn game_screen_with_shapes() {
+        let game = sample_game();
+        check_golden_with_theme("game_deuteranopia", &Theme::deuteranopia(), |fb, atlas, theme| draw_game(fb, &game, None, atlas, &worm_tiles(), theme, &Effects::new()));
+        check_golden_with_theme("game_high_contrast", &Theme::high_contrast(), |fb, atlas, theme| draw_game(fb, &game, None, atlas, &worm_tiles(), theme, &Effects::new()));
+    }
+
+    #[test]
+    fn game_screen_with_ghost() {
+        let mut rules = Rules::new(ARENA_WIDTH_BLOCKS, ARENA_HEIGHT_BLOCKS);
+        rules.obstacles = vec![(11, 11), (12, 11), (13, 11)];
+        let mut game = Game::with_rules(rules);
+        let sample = sample_game();
+        game.worm = sample.worm;
+        game.apples = sample.apples;
+        game.points = sample.points;
+        let mut ghost = sample_game();
+        ghost.worm = vec![(9, 6), (9, 7), (9, 8), (10, 8)];
+        ghost.direction = Direction::Up;
+        let hud = Hud { ghost_lead: Some(-25), ..sample_hud() };
+        check_golden("game_ghost", |fb, atlas, theme| {
+            draw_status_bar(fb, &game, &hud, atlas, theme);
+            draw_game(fb, &game, Some(&ghost), atlas, &worm_tiles(), theme, &Effects::new());
+        });
+    }
+
+    #[test]
+    fn game_over_screen() {
+        let game = sample_game();
+        check_golden("game_over_0", |fb, atlas, theme| {
+            draw_status_bar(fb, &game, &sample_hud(), atlas, theme);
+            draw_game_over(fb, &game, atlas, &worm_tiles(), theme, &Effects::new(), time(0));
+        });
+        check_golden("game_over_3", |fb, atlas, theme| draw_game_over(fb, &game, atlas, &worm_tiles(), theme, &Effects::new(), time(GAME_OVER_SCREEN_DELAY + 3 * GAME_OVER_SCREEN_SPEED)));
+        check_golden("game_over_end", |fb, atlas, theme| draw_game_over(fb, &game, atlas, &worm_tiles(), theme, &Effects::new(), time(GAME_OVER_SCREEN_DURATION)));
     }
 }
@@ -0,0 +1,256 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use gamepad;
+
+/// What the player wants to do, whichever key they pressed for it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    TurnUp,
+    TurnDown,
+    TurnLeft,
+    TurnRight,
+    /// Turn left from the direction the worm is going.
+    TurnRelativeLeft,
+    /// Turn right from the direction the worm is going.
+    TurnRelativeRight,
+    Pause,
+    Back,
+    Confirm,
+    Screenshot,
+}
+
+impl Action {
+    pub fn all() -> Vec<Action> {
+        use self::Action::*;
+        vec![TurnUp, TurnDown, TurnLeft, TurnRight, TurnRelativeLeft, TurnRelativeRight, Pause, Back, Confirm, Screenshot]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::TurnUp => "Up",
+            Action::TurnDown => "Down",
+            Action::TurnLeft => "Left",
+            Action::TurnRight => "Right",
+            Action::TurnRelativeLeft => "Turn left",
+            Action::TurnRelativeRight => "Turn right",
+            Action::Pause => "Pause",
+            Action::Back => "Back",
+            Action::Confirm => "Confirm",
+            Action::Screenshot => "Screenshot",
+        }
+    }
+
+    /// Name used in the config file.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Action::TurnUp => "turn_up",
+            Action::TurnDown => "turn_down",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::TurnRelativeLeft => "turn_relative_left",
+            Action::TurnRelativeRight => "turn_relative_right",
+            Action::Pause => "pause",
+            Action::Back => "back",
+            Action::Confirm => "confirm",
+            Action::Screenshot => "screenshot",
+        }
+    }
+}
+
+/// How the turn actions steer the worm.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ControlScheme {
+    /// Up, down, left and right go to those directions on the screen.
+    Absolute,
+    /// Only left and right, which turn relative to where the worm is going,
+    /// so that the game can be played with two buttons.
+    Relative,
+}
+
+impl ControlScheme {
+    pub fn all() -> Vec<ControlScheme> {
+        vec![ControlScheme::Absolute, ControlScheme::Relative]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlScheme::Absolute => "Absolute",
+            ControlScheme::Relative => "Relative",
+        }
+    }
+
+    /// Short name for the status bar.
+    pub fn tag(self) -> &'static str {
+        match self {
+            ControlScheme::Absolute => "ABS",
+            ControlScheme::Relative => "REL",
+        }
+    }
+}
+
+/// Ready made sets of bindings for the steering keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyPreset {
+    Arrows,
+    Wasd,
+    Hjkl,
+}
+
+impl KeyPreset {
+    pub fn all() -> Vec<KeyPreset> {
+        vec![KeyPreset::Arrows, KeyPreset::Wasd, KeyPreset::Hjkl]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyPreset::Arrows => "Arrows",
+            KeyPreset::Wasd => "WASD",
+            KeyPreset::Hjkl => "HJKL",
+        }
+    }
+}
+
+/// Which keys do which actions. A key does at most one action, but an
+/// action can have several keys.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Bindings {
+    keys: Vec<(Keycode, Action)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings::preset(KeyPreset::Arrows)
+    }
+}
+
+impl Bindings {
+    pub fn preset(preset: KeyPreset) -> Bindings {
+        use self::Action::*;
+        let steering = match preset {
+            KeyPreset::Arrows => [Keycode::Up, Keycode::Down, Keycode::Left, Keycode::Right, Keycode::Z, Keycode::X],
+            KeyPreset::Wasd => [Keycode::W, Keycode::S, Keycode::A, Keycode::D, Keycode::Q, Keycode::E],
+            KeyPreset::Hjkl => [Keycode::K, Keycode::J, Keycode::H, Keycode::L, Keycode::U, Keycode::I],
+        };
+        let actions = [TurnUp, TurnDown, TurnLeft, TurnRight, TurnRelativeLeft, TurnRelativeRight];
+        let mut keys: Vec<_> = steering.iter().cloned().zip(actions.iter().cloned()).collect();
+        keys.push((Keycode::P, Pause));
+        keys.push((Keycode::Escape, Back));
+        keys.push((Keycode::Return, Confirm));
+        keys.push((Keycode::F12, Screenshot));
+        Bindings { keys }
+    }
+
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        self.keys.iter().find(|binding| binding.0 == keycode).map(|binding| binding.1)
+    }
+
+    pub fn keys(&self, action: Action) -> Vec<Keycode> {
+        self.keys.iter().filter(|binding| binding.1 == action).map(|binding| binding.0).collect()
+    }
+
+    /// Makes `keycode` the only key for `action`, taking it away from
+    /// whatever it did before.
+    pub fn bind(&mut self, action: Action, keycode: Keycode) {
+        self.keys.retain(|&(key, bound)| key != keycode && bound != action);
+        self.keys.push((keycode, action));
+    }
+
+    /// Replaces the keys of `action` with keys named as in `key_name`,
+    /// separated by commas. Returns false if a name isn't known.
+    pub fn parse_keys(&mut self, action: Action, names: &str) -> bool {
+        let mut keys = Vec::new();
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match key_from_name(name) {
+                Some(keycode) => keys.push(keycode),
+                None => return false,
+            }
+        }
+        self.keys.retain(|&(key, bound)| !keys.contains(&key) && bound != action);
+        self.keys.extend(keys.into_iter().map(|key| (key, action)));
+        true
+    }
+
+    /// The keys of `action` as `parse_keys` takes them.
+    pub fn format_keys(&self, action: Action) -> String {
+        self.keys(action).into_iter().map(key_name).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// The key's name like "Up" or "Return". Unlike `Keycode::name` this works
+/// without SDL and the names are the same everywhere.
+pub fn key_name(keycode: Keycode) -> String {
+    format!("{:?}", keycode)
+}
+
+fn key_from_name(name: &str) -> Option<Keycode> {
+    // Printable keys have their ASCII code; the rest are scancodes with bit 30 set
+    (0..512).chain((1 << 30)..(1 << 30) + 512)
+        .filter_map(Keycode::from_i32)
+        .find(|&keycode| key_name(keycode).eq_ignore_ascii_case(name))
+}
+
+/// The action a key or controller button press does in the game.
+pub fn action(event: &Event, bindings: &Bindings) -> Option<Action> {
+    match *event {
+        Event::KeyDown { keycode: Some(keycode), .. } => bindings.action(keycode),
+        Event::ControllerButtonDown { button, .. } => gamepad::button_action(button),
+        _ => None,
+    }
+}
+
+/// Like `action`, but the arrow keys, Enter and Escape always work in menus
+/// no matter how they are bound, so that rebinding can't lock anyone out.
+pub fn menu_action(event: &Event, bindings: &Bindings) -> Option<Action> {
+    let keycode = match *event {
+        Event::KeyDown { keycode: Some(keycode), .. } => keycode,
+        _ => return action(event, bindings),
+    };
+    match keycode {
+        Keycode::Up => Some(Action::TurnUp),
+        Keycode::Down => Some(Action::TurnDown),
+        Keycode::Left => Some(Action::TurnLeft),
+        Keycode::Right => Some(Action::TurnRight),
+        Keycode::Return | Keycode::KpEnter | Keycode::Space => Some(Action::Confirm),
+        Keycode::Escape | Keycode::Backspace => Some(Action::Back),
+        _ => bindings.action(keycode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    #[test]
+    fn rebinding() {
+        let mut bindings = Bindings::preset(KeyPreset::Wasd);
+        assert_eq!(bindings.action(Keycode::W), Some(Action::TurnUp));
+        bindings.bind(Action::Pause, Keycode::W);
+        assert_eq!(bindings.action(Keycode::W), Some(Action::Pause));
+        assert_eq!(bindings.action(Keycode::P), None);
+        assert!(bindings.keys(Action::TurnUp).is_empty());
+    }
+
+    #[test]
+    fn keys_by_name() {
+        let mut bindings = Bindings::default();
+        assert!(bindings.parse_keys(Action::TurnUp, "Up, w, Kp8"));
+        assert_eq!(bindings.keys(Action::TurnUp), vec![Keycode::Up, Keycode::W, Keycode::Kp8]);
+        assert_eq!(bindings.format_keys(Action::TurnUp), "Up, W, Kp8");
+        assert!(!bindings.parse_keys(Action::TurnUp, "Nope"));
+    }
+
+    #[test]
+    fn fixed_menu_keys() {
+        let key = |keycode| Event::KeyDown { timestamp: 0, window_id: 0, keycode: Some(keycode), scancode: None, keymod: Mod::empty(), repeat: false };
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Pause, Keycode::Escape);
+        bindings.bind(Action::TurnUp, Keycode::Down);
+        assert_eq!(action(&key(Keycode::Escape), &bindings), Some(Action::Pause));
+        assert_eq!(menu_action(&key(Keycode::Escape), &bindings), Some(Action::Back));
+        assert_eq!(menu_action(&key(Keycode::Down), &bindings), Some(Action::TurnDown));
+        bindings.bind(Action::Back, Keycode::Q);
+        assert_eq!(menu_action(&key(Keycode::Q), &bindings), Some(Action::Back));
+    }
+}
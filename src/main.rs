@@ -4,9 +4,14 @@ extern crate rand;
 
 use sdl2::event::Event;
 use sdl2::event::WindowEvent;
+use sdl2::keyboard::{self, Keycode};
+use sdl2::video::FullscreenType;
 
 use game::Direction;
-use app::AppState;
+use app::{AppState, OptionItem, ControlItem};
+use input::Action;
+use mode::Mode;
+use renderer::{Letterbox, Renderer, SdlRenderer};
 
 use constants::*;
 
@@ -14,6 +19,26 @@ mod text;
 mod game;
 mod app;
 mod graphics;
+mod renderer;
+mod png;
+mod screenshot;
+mod gif;
+mod recording;
+mod theme;
+mod effects;
+mod audio;
+mod scores;
+mod menu;
+mod mode;
+mod config;
+mod speed;
+mod input;
+mod scoring;
+mod gamepad;
+mod pointer;
+mod daily;
+mod ghost;
+mod font;
 
 mod constants {
     use sdl2::pixels::Color;
@@ -29,9 +54,11 @@ mod constants {
     pub const WALL_COLOR: Color = Color { r: 0, g: 0, b: 200, a: 255 };
     pub const APPLE_COLOR: Color = Color { r: 200, g: 0, b: 0, a: 255 };
     pub const WORM_COLOR: Color = Color { r: 0, g: 200, b: 0, a: 255 };
+    pub const DEAD_WORM_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 255 };
 
     pub const BLOCK_SIZE: u32 = 16;
-    pub const STATUS_BAR_HEIGHT: u32 = BLOCK_SIZE * 2;
+    // Three rows of text, the last one for power-ups
+    pub const STATUS_BAR_HEIGHT: u32 = BLOCK_SIZE * 3;
 
     pub const ARENA_WIDTH_PX: u32 = ARENA_WIDTH_BLOCKS * BLOCK_SIZE;
     pub const ARENA_HEIGHT_PX: u32 = ARENA_HEIGHT_BLOCKS * BLOCK_SIZE;
@@ -42,13 +69,86 @@ mod constants {
     pub const INITIAL_STEP_DURATION: u32 = 500;
     pub const MIN_STEP_DURATION: u32 = 100;
     pub const STEP_DURATION_DECREMENT: u32 = 10;
+    pub const EXPONENTIAL_SPEED_FACTOR: f64 = 0.95;
+    pub const STEPPED_SPEED_APPLES: u32 = 5;
+    pub const STEPPED_SPEED_DECREMENT: u32 = 50;
+    pub const TIMED_SPEED_INTERVAL: u32 = 10000;
+
+    pub const MAX_APPLES: u32 = 5;
+    pub const GOLDEN_APPLE_LIFETIME: u32 = 5000;
+    /// Golden apples blink for this long before they go away
+    pub const GOLDEN_APPLE_WARNING: u32 = 1500;
+    pub const GOLDEN_APPLE_MULTIPLIER: u32 = 5;
+    pub const POISON_SHRINK: usize = 2;
+    pub const SLOW_APPLE_STEPS: u32 = 3;
+
+    /// One in this many apples brings a power-up along
+    pub const POWER_UP_CHANCE: u32 = 4;
+    /// Steps a power-up stays in the arena
+    pub const POWER_UP_LIFETIME: u32 = 40;
+    pub const SLOW_MOTION_FACTOR: u32 = 2;
+    pub const POWER_UP_MULTIPLIER: u32 = 2;
+
+    pub const TIME_ATTACK_DURATION: u32 = 120000;
+    pub const SURVIVAL_GROW_STEPS: u32 = 10;
+    pub const SPRINT_LENGTH: usize = 50;
+    pub const DAILY_MIN_OBSTACLES: u32 = 6;
+    pub const DAILY_MAX_OBSTACLES: u32 = 10;
+    pub const DAILY_MAX_OBSTACLE_LENGTH: u32 = 4;
+    /// Opacity of the replayed worm raced against in seeded modes
+    pub const GHOST_ALPHA: u8 = 80;
 
     pub const START_SCREEN_SPEED: u32 = 250;
+    pub const MENU_TITLE_Y: i32 = 48;
+    pub const MENU_TOP: i32 = 100;
+    pub const MENU_LINE_HEIGHT: i32 = 22;
+    pub const TEXT_LINE_HEIGHT: i32 = 16;
+
     pub const GAME_OVER_SCREEN_DELAY: u32 = 1000;
     pub const GAME_OVER_SCREEN_SPEED: u32 = 100;
     pub const GAME_OVER_SCREEN_DURATION: u32 = 15000;
+
+    pub const PARTICLE_COUNT: u32 = 12;
+    pub const PARTICLE_LIFETIME: u32 = 600;
+    pub const PARTICLE_GRAVITY: f32 = 0.0003;
+    pub const POPUP_LIFETIME: u32 = 800;
+    pub const POPUP_RISE: u32 = 16;
+    pub const SHAKE_DURATION: u32 = 400;
+    pub const SHAKE_AMPLITUDE: u32 = 4;
+
+    pub const SCREENSHOT_DIR: &str = "screenshots";
+    pub const RECORDING_DIR: &str = "recordings";
+    pub const REPLAY_DIR: &str = "replays";
+    pub const THEME_DIR: &str = "themes";
+    pub const HIGH_SCORE_FILE: &str = "highscores.txt";
+    pub const CONFIG_FILE: &str = "mato.cfg";
+    pub const MAX_VOLUME: u32 = 10;
+    pub const TOAST_DURATION: u32 = 1500;
+    /// How far a stick has to be pushed, out of 32767, to steer
+    pub const STICK_DEADZONE: i32 = 8000;
+    /// Logical pixels a press has to move to be a swipe rather than a tap
+    pub const SWIPE_DISTANCE: i32 = 12;
 }
 
+/// Hotkeys that work everywhere and can't be rebound.
+const FIXED_KEYS_TEXT: &[&str] = &[
+    "ARROWS, ENTER, ESC  MENUS",
+    "F3  DEBUG OVERLAY",
+    "F6  SOUND ON/OFF",
+    "F7  NEXT THEME",
+    "F9  RECORD GIF",
+    "F11, ALT+ENTER  FULLSCREEN",
+];
+
+const CREDITS_TEXT: &[&str] = &[
+    "MATO",
+    "",
+    "BY ILKKA RAUTA",
+    "AND CONTRIBUTORS",
+    "",
+    "MADE WITH RUST AND SDL2",
+];
+
 #[derive(Clone, Copy)]
 pub struct TimeUpdate {
     absolute: u32,
@@ -57,16 +157,54 @@ pub struct TimeUpdate {
 
 pub struct EventIterator<'a> {
     internal_events: &'a mut sdl2::event::EventPollIterator<'a>,
+    gamepads: &'a mut gamepad::Gamepads,
+    /// For giving mouse events in logical coordinates
+    letterbox: Letterbox,
     redraw_needed: bool,
-    quit_requested: bool
+    quit_requested: bool,
+    screenshot_requested: bool,
+    /// Keys bound to `Action::Screenshot`, which work on every screen
+    screenshot_keys: Vec<Keycode>,
+    recording_toggled: bool,
+    fullscreen_toggled: bool,
+    theme_switch_requested: bool,
+    mute_toggled: bool,
+    debug_overlay_toggled: bool,
+    menu_moved: bool,
 }
 
 impl<'a> EventIterator<'a> {
-    fn new(internal_events: &'a mut sdl2::event::EventPollIterator<'a>) -> EventIterator<'a> {
+    fn new(internal_events: &'a mut sdl2::event::EventPollIterator<'a>, gamepads: &'a mut gamepad::Gamepads, letterbox: Letterbox, screenshot_keys: Vec<Keycode>) -> EventIterator<'a> {
         EventIterator {
             internal_events: internal_events,
+            gamepads,
+            letterbox,
             redraw_needed: false,
             quit_requested: false,
+            screenshot_requested: false,
+            screenshot_keys,
+            recording_toggled: false,
+            fullscreen_toggled: false,
+            theme_switch_requested: false,
+            mute_toggled: false,
+            debug_overlay_toggled: false,
+            menu_moved: false,
+        }
+    }
+}
+
+impl<'a> EventIterator<'a> {
+    fn to_logical(&self, event: Event) -> Event {
+        match event {
+            Event::MouseButtonDown { timestamp, window_id, which, mouse_btn, x, y } => {
+                let (x, y) = self.letterbox.to_logical(x, y);
+                Event::MouseButtonDown { timestamp, window_id, which, mouse_btn, x, y }
+            },
+            Event::MouseButtonUp { timestamp, window_id, which, mouse_btn, x, y } => {
+                let (x, y) = self.letterbox.to_logical(x, y);
+                Event::MouseButtonUp { timestamp, window_id, which, mouse_btn, x, y }
+            },
+            _ => event,
         }
     }
 }
@@ -76,14 +214,43 @@ impl<'a> Iterator for EventIterator<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let event = self.internal_events.next();
+            let event = match self.internal_events.next() {
+                Some(event) => self.gamepads.translate(self.to_logical(event)),
+                None => return None,
+            };
+            if event.is_none() {
+                continue;
+            }
             match event {
                 Some(Event::Quit {..}) => {
                     self.quit_requested = true;
                 },
-                Some(Event::Window {win_event: WindowEvent::Exposed, ..}) => {
+                Some(Event::Window {win_event: WindowEvent::Exposed, ..}) |
+                Some(Event::Window {win_event: WindowEvent::SizeChanged(..), ..}) => {
                     self.redraw_needed = true;
                 },
+                Some(Event::KeyDown {keycode: Some(keycode), repeat: false, ..}) if self.screenshot_keys.contains(&keycode) => {
+                    self.screenshot_requested = true;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::F9), repeat: false, ..}) => {
+                    self.recording_toggled = !self.recording_toggled;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::F7), repeat: false, ..}) => {
+                    self.theme_switch_requested = true;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::F6), repeat: false, ..}) => {
+                    self.mute_toggled = !self.mute_toggled;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::F3), repeat: false, ..}) => {
+                    self.debug_overlay_toggled = !self.debug_overlay_toggled;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::F11), repeat: false, ..}) => {
+                    self.fullscreen_toggled = !self.fullscreen_toggled;
+                },
+                Some(Event::KeyDown {keycode: Some(Keycode::Return), keymod, repeat: false, ..})
+                    if keymod.intersects(keyboard::LALTMOD | keyboard::RALTMOD) => {
+                    self.fullscreen_toggled = !self.fullscreen_toggled;
+                },
                 _ => {
                     return event;
                 }
@@ -96,25 +263,64 @@ fn main() {
     let ctx = sdl2::init().expect("Initializing SDL 2");
     let video_ctx = ctx.video().expect("Getting video subsystem");
 
-    let window  = video_ctx
-        .window("Mato", WINDOW_WIDTH, WINDOW_HEIGHT)
+    // Start with the largest integer scale that leaves some room on the desktop
+    let initial_scale = match video_ctx.desktop_display_mode(0) {
+        Ok(mode) => std::cmp::max(1, std::cmp::min(
+            mode.w as u32 * 3 / 4 / WINDOW_WIDTH,
+            mode.h as u32 * 3 / 4 / WINDOW_HEIGHT)),
+        Err(_) => 1,
+    };
+    let mut window  = video_ctx
+        .window("Mato", WINDOW_WIDTH * initial_scale, WINDOW_HEIGHT * initial_scale)
         .position_centered()
+        .resizable()
         .opengl()
         .build()
         .expect("Creating window");
+    window.set_minimum_size(WINDOW_WIDTH, WINDOW_HEIGHT).expect("Setting minimum window size");
 
-    let mut canvas = window
+    let canvas = window
         .into_canvas()
         .build()
         .expect("Creating canvas");
 
     let texture_creator = canvas.texture_creator();
-    let mut atlas = text::GlyphAtlas::new(&texture_creator);
+    let mut renderer = SdlRenderer::new(canvas, &texture_creator, WINDOW_WIDTH, WINDOW_HEIGHT);
+    let default_atlas = text::GlyphAtlas::new();
+    let worm_tiles = graphics::worm_tiles();
+    let themes = theme::all_themes();
+    // Themes may bring their own fonts; the ones that can't be loaded get the built-in one
+    let theme_atlases: Vec<_> = themes.iter().map(|theme| {
+        let path = std::path::Path::new(THEME_DIR).join(theme.font.as_ref()?);
+        font::load(&path).map_err(|e| eprintln!("Could not load font {}: {}", path.display(), e)).ok()
+    }).collect();
+    let mut theme_index = 0;
+    let mut settings = config::Settings::load();
+    // What the game, window and audio were last set up with
+    let mut applied_settings: Option<config::Settings> = None;
 
     let mut timer = ctx.timer().expect("Getting timer subsystem");
     let mut game = game::Game::new(ARENA_WIDTH_BLOCKS, ARENA_HEIGHT_BLOCKS);
+    let mut effects = effects::Effects::new();
+    let mut audio = audio::Audio::new(&ctx);
+    let mut mode = Mode::Classic;
+    let mut menus = app::Menus::new();
+    let mut high_scores = scores::HighScores::load();
+    // The best run so far, raced against in modes that are the same every time
+    let mut ghost: Option<ghost::Ghost> = None;
+    // Controllers are optional, so don't give up if they can't be used
+    let mut gamepads = gamepad::Gamepads::new(ctx.game_controller().map_err(|e| {
+        eprintln!("Game controllers not available: {}", e);
+    }).ok());
+    let mut gamepad_count = 0;
+    let mut pointer = pointer::Pointer::new();
 
     let mut redraw_needed = true;
+    let mut screenshot_requested = false;
+    // Message shown on top of everything else, and the time until it is shown
+    let mut toast: Option<(String, u32)> = None;
+    let mut recorder: Option<recording::Recorder> = None;
+    let mut debug_overlay = false;
 
     let mut events = ctx.event_pump().expect("Getting event pump");
     let mut now = timer.ticks();
@@ -129,42 +335,297 @@ fn main() {
         let time_update = TimeUpdate { absolute: now - state_start, diff: time_diff };
 
         let mut poll_iter = events.poll_iter();
-        let mut event_iterator = EventIterator::new(&mut poll_iter);
+        let mut event_iterator = EventIterator::new(&mut poll_iter, &mut gamepads, renderer.letterbox(), settings.bindings.keys(Action::Screenshot));
 
         let (new_state, redraw_requested) = match state {
-            AppState::Start => app::start(&mut event_iterator, time_update, &mut game),
-            AppState::Game => app::game(&mut event_iterator, time_update, &mut game),
-            AppState::GameOver => app::game_over(&mut event_iterator, time_update),
+            AppState::Start => app::start(&mut event_iterator, time_update, &mut menus, &mut game, &settings.bindings),
+            AppState::ModeSelect => app::mode_select(&mut event_iterator, &mut menus.mode, &mut mode, &settings.bindings),
+            AppState::Options => app::options(&mut event_iterator, &mut menus, &mut settings, &themes),
+            AppState::Controls => app::controls(&mut event_iterator, &mut menus, &mut settings.bindings),
+            AppState::HighScores | AppState::Credits | AppState::ScoreBreakdown => app::info_screen(&mut event_iterator, state, AppState::Start),
+            AppState::FixedKeys => app::info_screen(&mut event_iterator, state, AppState::Controls),
+            AppState::Game => app::game(&mut event_iterator, time_update, &mut game, &settings.bindings, settings.control_scheme, &mut pointer),
+            AppState::Paused => app::paused(&mut event_iterator, &mut game, &settings.bindings),
+            AppState::GameOver => app::game_over(&mut event_iterator, time_update, &settings.bindings),
             AppState::Quit => break
         };
         if event_iterator.quit_requested {
             break;
         }
 
+        let game_events = game.take_events();
+        let effects_were_active = effects.is_active();
+        effects.update(time_diff);
+        for game_event in &game_events {
+            effects.handle(game_event);
+            audio.handle(game_event);
+        }
+        let effects_redraw = effects_were_active || effects.is_active();
+
+        screenshot_requested = screenshot_requested || event_iterator.screenshot_requested;
+        if event_iterator.fullscreen_toggled {
+            settings.fullscreen = !settings.fullscreen;
+        }
+        if event_iterator.theme_switch_requested {
+            let next = &themes[(theme_index + 1) % themes.len()];
+            settings.theme = next.name.clone();
+            toast = Some((next.name.to_uppercase(), now + TOAST_DURATION));
+        }
+        if applied_settings.as_ref() != Some(&settings) {
+            theme_index = themes.iter().position(|theme| theme.name == settings.theme).unwrap_or(0);
+            audio.set_volume(settings.volume as f32 / MAX_VOLUME as f32);
+            let window = renderer.canvas_mut().window_mut();
+            let fullscreen = if settings.fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+            if window.fullscreen_state() != fullscreen {
+                if let Err(e) = window.set_fullscreen(fullscreen) {
+                    eprintln!("Could not toggle fullscreen: {}", e);
+                }
+            }
+            if applied_settings.is_some() {
+                if let Err(e) = settings.save() {
+                    eprintln!("Could not save settings: {}", e);
+                }
+            }
+            applied_settings = Some(settings.clone());
+            redraw_needed = true;
+        }
+        // New rules start a new game, so they are only picked up in the menus.
+        // That's also where the daily challenge moves on to the next day.
+        if state.is_menu() {
+            let rules = mode.rules(settings.rules());
+            if *game.rules() != rules {
+                game.set_rules(rules);
+            }
+        }
+        if event_iterator.menu_moved {
+            audio.play(audio::Sound::MenuBlip);
+        }
+        if event_iterator.debug_overlay_toggled {
+            debug_overlay = !debug_overlay;
+            redraw_needed = true;
+        }
+        if event_iterator.mute_toggled {
+            let message = if audio.toggle_muted() { "MUTED" } else { "SOUND ON" };
+            toast = Some((message.to_string(), now + TOAST_DURATION));
+            redraw_needed = true;
+        }
+        if event_iterator.gamepads.count() != gamepad_count {
+            let message = if event_iterator.gamepads.count() > gamepad_count { "CONTROLLER CONNECTED" } else { "CONTROLLER REMOVED" };
+            gamepad_count = event_iterator.gamepads.count();
+            toast = Some((message.to_string(), now + TOAST_DURATION));
+            redraw_needed = true;
+        }
+        if event_iterator.recording_toggled {
+            let message = match recorder.take() {
+                Some(finished) => match finished.finish(now) {
+                    Ok(path) => {
+                        println!("Saved recording to {}", path.display());
+                        "SAVED"
+                    },
+                    Err(e) => {
+                        eprintln!("Could not save recording: {}", e);
+                        "RECORDING FAILED"
+                    }
+                },
+                None => match recording::Recorder::start(WINDOW_WIDTH, WINDOW_HEIGHT, &themes[theme_index].palette()) {
+                    Ok(started) => {
+                        recorder = Some(started);
+                        "REC"
+                    },
+                    Err(e) => {
+                        eprintln!("Could not start recording: {}", e);
+                        "RECORDING FAILED"
+                    }
+                }
+            };
+            toast = Some((message.to_string(), now + TOAST_DURATION));
+            redraw_needed = true;
+        }
+
         let state_changed = state != new_state;
         state = new_state;
         if state_changed {
+            if state == AppState::Start {
+                effects.clear();
+            }
+            if state == AppState::Game || state.is_menu() {
+                audio.play(audio::Sound::MenuBlip);
+            }
+            if state == AppState::Game && game.steps() == 0 && game.rules().seed.is_some() {
+                ghost = ghost::Replay::load(&mode.table(game.rules())).map(|replay| ghost::Ghost::new(game.rules().clone(), replay));
+            } else if state.is_menu() {
+                ghost = None;
+            }
+            if state == AppState::GameOver {
+                let table = mode.table(game.rules());
+                // Later attempts at the daily challenge count for neither the
+                // high score nor the ghost
+                let played = mode.one_attempt() && high_scores.best(&table).is_some();
+                if let (Some(score), Some(_)) = (mode.score(&game), game.rules().seed) {
+                    let best = ghost::Replay::load(&table).map(|replay| replay.score);
+                    if !played && best.is_none_or(|best| mode.is_better(score, best)) {
+                        let replay = ghost::Replay { score, inputs: game.inputs().to_vec(), tick_times: game.tick_times().to_vec() };
+                        if let Err(e) = replay.save(&table) {
+                            eprintln!("Could not save replay: {}", e);
+                        }
+                    }
+                }
+                let new_high_score = !played && mode.score(&game)
+                    .is_some_and(|score| high_scores.record(&table, score, mode.lower_is_better()));
+                if new_high_score {
+                    if let Err(e) = high_scores.save() {
+                        eprintln!("Could not save high scores: {}", e);
+                    }
+                    toast = Some(("NEW HIGH SCORE".to_string(), now + TOAST_DURATION));
+                } else if played {
+                    toast = Some(("ALREADY PLAYED TODAY".to_string(), now + TOAST_DURATION));
+                }
+            }
+            audio.set_music_playing(state == AppState::Game);
             // Run the state updater at least once before calling the render fn
             redraw_needed = true;
             state_start = now;
             continue;
         }
-        redraw_needed = redraw_requested || redraw_needed || event_iterator.redraw_needed;
+        redraw_needed = redraw_requested || redraw_needed || event_iterator.redraw_needed || screenshot_requested || effects_redraw;
+        if toast.as_ref().is_some_and(|&(_, until)| now >= until) {
+            toast = None;
+            redraw_needed = true;
+        }
 
-        if redraw_needed {
-            canvas.set_draw_color(BACKGROUND_COLOR);
-            canvas.clear();
+        if let Some(ghost) = ghost.as_mut() {
+            ghost.catch_up(game.steps());
+        }
 
+        if redraw_needed {
+            let theme = &themes[theme_index];
+            let atlas = theme_atlases[theme_index].as_ref().unwrap_or(&default_atlas);
+            renderer.clear(theme.background);
+
+            if state == AppState::Game || state == AppState::Paused || state == AppState::GameOver {
+                // Points modes show the score to beat going up as it's beaten
+                let best = high_scores.best(&mode.table(game.rules()));
+                let best = if mode.scores_points() { Some(best.unwrap_or(0).max(game.points)) } else { best };
+                let high_score = best.map_or("-".to_string(), |score| mode.format_score(score));
+                let hud = graphics::Hud {
+                    mode: mode.name(),
+                    high_score: &high_score,
+                    control_scheme: settings.control_scheme,
+                    ghost_lead: ghost.as_ref().map(|ghost| game.points as i32 - ghost.game().points as i32),
+                };
+                graphics::draw_status_bar(&mut renderer, &game, &hud, atlas, theme);
+            }
             match state {
-                AppState::Start => graphics::draw_start_screen(&mut canvas, &mut atlas, time_update),
-                AppState::Game => graphics::draw_game(&mut canvas, &game, &mut atlas),
-                AppState::GameOver => graphics::draw_game_over(&mut canvas, &game, &mut atlas, time_update),
+                AppState::Start => {
+                    let labels: Vec<_> = menus.main.items().iter().map(|item| item.label()).collect();
+                    graphics::draw_start_screen(&mut renderer, atlas, theme, time_update, &labels, menus.main.selected_index());
+                },
+                AppState::ModeSelect => {
+                    let labels: Vec<_> = menus.mode.items().iter().map(|mode| mode.name().to_uppercase()).collect();
+                    graphics::draw_menu(&mut renderer, atlas, theme, "MODE", &labels, menus.mode.selected_index());
+                },
+                AppState::Options => {
+                    let on_off = |on: bool| if on { "ON" } else { "OFF" };
+                    let labels: Vec<_> = menus.options.items().iter().map(|item| match *item {
+                        OptionItem::SpeedCurve => format!("SPEED  {}", settings.speed_curve.name().to_uppercase()),
+                        OptionItem::ArenaSize => format!("ARENA  {}", settings.arena_size.name().to_uppercase()),
+                        OptionItem::Wrap => format!("WRAP AROUND  {}", on_off(settings.wrap)),
+                        OptionItem::Apples => format!("APPLES  {}", settings.apple_count),
+                        OptionItem::Theme => format!("THEME  {}", theme.name.to_uppercase()),
+                        OptionItem::Volume => format!("VOLUME  {}", settings.volume),
+                        OptionItem::Fullscreen => format!("FULLSCREEN  {}", on_off(settings.fullscreen)),
+                        OptionItem::ControlScheme => format!("STEERING  {}", settings.control_scheme.name().to_uppercase()),
+                        OptionItem::Keys => "KEY BINDINGS".to_string(),
+                        OptionItem::Back => "BACK".to_string(),
+                    }).collect();
+                    graphics::draw_menu(&mut renderer, atlas, theme, "OPTIONS", &labels, menus.options.selected_index());
+                },
+                AppState::HighScores => {
+                    let lines: Vec<_> = Mode::all().iter()
+                        .map(|mode| {
+                            let best = high_scores.best(&mode.table(&mode.rules(settings.rules()))).map_or("-".to_string(), |score| mode.format_score(score));
+                            format!("{}  {}", mode.name().to_uppercase(), best)
+                        })
+                        .collect();
+                    graphics::draw_text_screen(&mut renderer, atlas, theme, "HIGH SCORES", &lines);
+                },
+                AppState::Controls => {
+                    let labels: Vec<_> = menus.controls.items().iter().map(|item| match *item {
+                        ControlItem::Bind(action) if menus.rebinding == Some(action) => "PRESS A KEY".to_string(),
+                        ControlItem::Bind(action) => {
+                            let keys = settings.bindings.format_keys(action);
+                            format!("{}  {}", action.name(), if keys.is_empty() { "NONE" } else { &keys[..] }).to_uppercase()
+                        },
+                        ControlItem::Preset(preset) => format!("USE {} KEYS", preset.name().to_uppercase()),
+                        ControlItem::FixedKeys => "OTHER KEYS".to_string(),
+                        ControlItem::Back => "BACK".to_string(),
+                    }).collect();
+                    graphics::draw_menu(&mut renderer, atlas, theme, "CONTROLS", &labels, menus.controls.selected_index());
+                },
+                AppState::FixedKeys => graphics::draw_text_screen(&mut renderer, atlas, theme, "OTHER KEYS", FIXED_KEYS_TEXT),
+                AppState::Credits => graphics::draw_text_screen(&mut renderer, atlas, theme, "CREDITS", CREDITS_TEXT),
+                AppState::ScoreBreakdown => {
+                    let breakdown = game.breakdown();
+                    let mut lines: Vec<_> = breakdown.lines.iter().map(|&(name, points)| format!("{}  {}", name, points)).collect();
+                    lines.push(String::new());
+                    lines.push(format!("TOTAL  {}", breakdown.total()));
+                    graphics::draw_text_screen(&mut renderer, atlas, theme, "SCORE", &lines);
+                },
+                AppState::Game => graphics::draw_game(&mut renderer, &game, ghost.as_ref().map(|ghost| ghost.game()), atlas, &worm_tiles, theme, &effects),
+                AppState::Paused => {
+                    graphics::draw_game(&mut renderer, &game, ghost.as_ref().map(|ghost| ghost.game()), atlas, &worm_tiles, theme, &effects);
+                    graphics::draw_paused(&mut renderer, atlas, theme);
+                },
+                AppState::GameOver => graphics::draw_game_over(&mut renderer, &game, atlas, &worm_tiles, theme, &effects, time_update),
                 AppState::Quit => unreachable!()
             }
+            if debug_overlay && !state.is_menu() {
+                graphics::draw_debug_overlay(&mut renderer, atlas, theme, &game);
+            }
 
             redraw_needed = false;
 
-            canvas.present();
+            if let Some(mut active) = recorder.take() {
+                let recorded = renderer.read_pixels().and_then(|image| {
+                    active.add_frame(&image, now).map_err(|e| e.to_string())
+                });
+                match recorded {
+                    Ok(()) => recorder = Some(active),
+                    Err(e) => {
+                        eprintln!("Recording stopped: {}", e);
+                        toast = Some(("RECORDING FAILED".to_string(), now + TOAST_DURATION));
+                    }
+                }
+            }
+            if screenshot_requested {
+                screenshot_requested = false;
+                let saved = renderer.read_pixels().and_then(|image| {
+                    screenshot::save(&image).map_err(|e| e.to_string())
+                });
+                let message = match saved {
+                    Ok(path) => {
+                        println!("Saved screenshot to {}", path.display());
+                        "SAVED"
+                    },
+                    Err(e) => {
+                        eprintln!("Could not save screenshot: {}", e);
+                        "SCREENSHOT FAILED"
+                    }
+                };
+                toast = Some((message.to_string(), now + TOAST_DURATION));
+            }
+            if let Some((ref text, _)) = toast {
+                graphics::draw_toast(&mut renderer, atlas, theme, text);
+            }
+
+            renderer.present();
+        }
+    }
+
+    if let Some(active) = recorder {
+        match active.finish(timer.ticks()) {
+            Ok(path) => println!("Saved recording to {}", path.display()),
+            Err(e) => eprintln!("Could not save recording: {}", e),
         }
     }
 }
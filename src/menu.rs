@@ -0,0 +1,86 @@
+use input::Action;
+
+/// What an action did to a menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuInput<T> {
+    /// The selection moved to another item.
+    Moved,
+    Chosen(T),
+    /// Left or right on an item, for changing a value in place.
+    Adjusted(T, i32),
+    Back,
+}
+
+/// A vertical list of items with one of them selected. The items are
+/// whatever the screen wants to get back when one is chosen; drawing them is
+/// up to the screen, see `graphics::draw_menu`.
+pub struct Menu<T: Copy> {
+    items: Vec<T>,
+    selected: usize,
+}
+
+impl<T: Copy> Menu<T> {
+    pub fn new(items: Vec<T>) -> Menu<T> {
+        assert!(!items.is_empty(), "Menu without items");
+        Menu { items, selected: 0 }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> T {
+        self.items[self.selected]
+    }
+
+    /// Clicking an item selects and chooses it at once.
+    pub fn click(&mut self, index: usize) -> MenuInput<T> {
+        self.selected = index;
+        MenuInput::Chosen(self.selected())
+    }
+
+    /// Up and down move the selection, wrapping around at the ends; left and
+    /// right adjust the selected item.
+    pub fn handle(&mut self, action: Action) -> Option<MenuInput<T>> {
+        let count = self.items.len();
+        match action {
+            Action::TurnUp => {
+                self.selected = (self.selected + count - 1) % count;
+                Some(MenuInput::Moved)
+            },
+            Action::TurnDown => {
+                self.selected = (self.selected + 1) % count;
+                Some(MenuInput::Moved)
+            },
+            Action::TurnLeft => Some(MenuInput::Adjusted(self.selected(), -1)),
+            Action::TurnRight => Some(MenuInput::Adjusted(self.selected(), 1)),
+            Action::Confirm => Some(MenuInput::Chosen(self.selected())),
+            Action::Back => Some(MenuInput::Back),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigation() {
+        let mut menu = Menu::new(vec!['a', 'b', 'c']);
+        assert_eq!(menu.handle(Action::TurnUp), Some(MenuInput::Moved));
+        assert_eq!(menu.selected(), 'c');
+        assert_eq!(menu.handle(Action::TurnDown), Some(MenuInput::Moved));
+        assert_eq!(menu.handle(Action::TurnDown), Some(MenuInput::Moved));
+        assert_eq!(menu.handle(Action::Confirm), Some(MenuInput::Chosen('b')));
+        assert_eq!(menu.handle(Action::TurnLeft), Some(MenuInput::Adjusted('b', -1)));
+        assert_eq!(menu.handle(Action::Back), Some(MenuInput::Back));
+        assert_eq!(menu.handle(Action::Pause), None);
+        assert_eq!(menu.click(0), MenuInput::Chosen('a'));
+        assert_eq!(menu.selected_index(), 0);
+    }
+}
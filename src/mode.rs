@@ -0,0 +1,142 @@
+use constants::*;
+use daily::{self, Date};
+use game::{Game, Rules};
+
+/// The kinds of game that can be picked from the mode menu. Each keeps its
+/// own high score.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Until the worm dies.
+    Classic,
+    /// As many points as possible in `TIME_ATTACK_DURATION`.
+    TimeAttack,
+    /// No apples, but the worm grows every `SURVIVAL_GROW_STEPS` steps;
+    /// scored by how long it stays alive.
+    Survival,
+    /// Scored by how quickly the worm gets to `SPRINT_LENGTH`.
+    Sprint,
+    /// The same board and rules for everyone on the same day, with one
+    /// scored attempt.
+    Daily,
+}
+
+impl Mode {
+    pub fn all() -> Vec<Mode> {
+        vec![Mode::Classic, Mode::TimeAttack, Mode::Survival, Mode::Sprint, Mode::Daily]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Classic => "Classic",
+            Mode::TimeAttack => "Time Attack",
+            Mode::Survival => "Survival",
+            Mode::Sprint => "Sprint",
+            Mode::Daily => "Daily",
+        }
+    }
+
+    /// The high score table's name for a game of the mode with `rules`. Each
+    /// day's challenge has its own, which also shows whether it has been
+    /// played. It goes by the day the board was made for, not the day the
+    /// game ends on, which may be the next one.
+    pub fn table(self, rules: &Rules) -> String {
+        match self {
+            Mode::Daily => {
+                let date = rules.seed.map_or_else(Date::today, Date::from_seed);
+                format!("{} {}", self.name(), date)
+            },
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// Only the first game of the table counts.
+    pub fn one_attempt(self) -> bool {
+        self == Mode::Daily
+    }
+
+    /// `rules`, from the options, changed for this mode. The daily challenge
+    /// replaces them with today's.
+    pub fn rules(self, mut rules: Rules) -> Rules {
+        match self {
+            Mode::Classic => {},
+            Mode::TimeAttack => rules.time_limit = Some(TIME_ATTACK_DURATION),
+            Mode::Survival => {
+                rules.apple_count = 0;
+                rules.grow_every = Some(SURVIVAL_GROW_STEPS);
+            },
+            Mode::Sprint => rules.target_length = Some(SPRINT_LENGTH),
+            Mode::Daily => return daily::rules(Date::today()),
+        }
+        rules
+    }
+
+    /// Whether the score is the game's points rather than a time.
+    pub fn scores_points(self) -> bool {
+        match self {
+            Mode::Classic | Mode::TimeAttack | Mode::Daily => true,
+            Mode::Survival | Mode::Sprint => false,
+        }
+    }
+
+    /// Sprint times are better the lower they are; everything else the higher.
+    pub fn lower_is_better(self) -> bool {
+        self == Mode::Sprint
+    }
+
+    /// Whether `score` beats `best`.
+    pub fn is_better(self, score: u32, best: u32) -> bool {
+        if self.lower_is_better() { score < best } else { score > best }
+    }
+
+    /// What goes on the high score table for a game that has ended: points,
+    /// or milliseconds for the timed modes. None if the game doesn't count,
+    /// like a sprint that ended with the worm dying.
+    pub fn score(self, game: &Game) -> Option<u32> {
+        match self {
+            Mode::Classic | Mode::TimeAttack | Mode::Daily => Some(game.points),
+            Mode::Survival => Some(game.elapsed()),
+            Mode::Sprint => if game.finished { Some(game.elapsed()) } else { None },
+        }
+    }
+
+    pub fn format_score(self, score: u32) -> String {
+        if self.scores_points() { score.to_string() } else { format_time(score) }
+    }
+}
+
+/// Minutes, seconds and milliseconds, like 1:05.250.
+pub fn format_time(milliseconds: u32) -> String {
+    let seconds = milliseconds / 1000;
+    format!("{}:{:02}.{:03}", seconds / 60, seconds % 60, milliseconds % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores() {
+        let mut game = Game::with_rules(Mode::Sprint.rules(Rules::new(20, 15)));
+        game.points = 40;
+        game.tick(65250);
+        assert_eq!(Mode::Classic.score(&game), Some(40));
+        assert_eq!(Mode::Sprint.score(&game), None);
+        game.finished = true;
+        assert_eq!(Mode::Sprint.score(&game).map(|score| Mode::Sprint.format_score(score)), Some("1:05.250".to_string()));
+
+        // A time that happens to equal the points is still a time
+        let game = Game::with_rules(Mode::Survival.rules(Rules::new(20, 15)));
+        assert_eq!(Mode::Survival.score(&game), Some(game.points));
+        assert!(!Mode::Survival.scores_points());
+        assert!(Mode::Daily.scores_points());
+    }
+
+    #[test]
+    fn daily_table_across_midnight() {
+        // Started on an earlier day's board and ending today
+        let game = Game::with_rules(daily::rules(Date { year: 2020, month: 2, day: 29 }));
+        assert_eq!(Mode::Daily.table(game.rules()), "Daily 2020-02-29");
+        assert_eq!(Mode::Daily.table(&Mode::Daily.rules(Rules::new(20, 15))), format!("Daily {}", Date::today()));
+        assert_eq!(Mode::Classic.table(game.rules()), "Classic");
+    }
+}
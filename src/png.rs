@@ -0,0 +1,474 @@
+#![allow(dead_code)]
+
+//! Just enough PNG to save and load 8-bit RGBA images.
+//!
+//! The encoder uses fixed Huffman codes and only looks for repeats of the
+//! previous pixel and of the row above, which is all the game's flat colored
+//! screens need to compress well. The decoder handles any deflate stream but
+//! only 8-bit RGBA, non-interlaced images.
+
+use std::fmt;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug)]
+pub enum PngError {
+    NotPng,
+    Unsupported(&'static str),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PngError::NotPng => write!(f, "not a PNG file"),
+            PngError::Unsupported(what) => write!(f, "unsupported PNG: {}", what),
+            PngError::Corrupt(what) => write!(f, "corrupt PNG: {}", what),
+        }
+    }
+}
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA, row by row.
+    pub pixels: Vec<u8>,
+}
+
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), (width * height * 4) as usize);
+    let stride = (width * 4) as usize;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgba.chunks(stride) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    push_u32(&mut header, width);
+    push_u32(&mut header, height);
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_compress(&raw, stride + 1));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, PngError> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(PngError::NotPng);
+    }
+    let mut rest = &data[SIGNATURE.len()..];
+    let mut header = None;
+    let mut compressed = Vec::new();
+    while rest.len() >= 12 {
+        let length = read_u32(rest) as usize;
+        if rest.len() < length + 12 {
+            return Err(PngError::Corrupt("truncated chunk"));
+        }
+        let kind = &rest[4..8];
+        let body = &rest[8..8 + length];
+        if read_u32(&rest[8 + length..]) != crc32(&rest[4..8 + length]) {
+            return Err(PngError::Corrupt("chunk checksum mismatch"));
+        }
+        match kind {
+            b"IHDR" => header = Some(body.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[12 + length..];
+    }
+    let header = header.ok_or(PngError::Corrupt("missing IHDR"))?;
+    if header.len() != 13 {
+        return Err(PngError::Corrupt("bad IHDR"));
+    }
+    let width = read_u32(&header[0..]);
+    let height = read_u32(&header[4..]);
+    if header[8] != 8 || header[9] != 6 {
+        return Err(PngError::Unsupported("only 8-bit RGBA is supported"));
+    }
+    if header[12] != 0 {
+        return Err(PngError::Unsupported("interlacing"));
+    }
+
+    let raw = zlib_decompress(&compressed)?;
+    let stride = (width * 4) as usize;
+    if raw.len() != (stride + 1) * height as usize {
+        return Err(PngError::Corrupt("wrong amount of image data"));
+    }
+    let mut pixels: Vec<u8> = Vec::with_capacity(stride * height as usize);
+    for (y, row) in raw.chunks(stride + 1).enumerate() {
+        let filter = row[0];
+        for x in 0..stride {
+            let a = if x >= 4 { pixels[y * stride + x - 4] } else { 0 };
+            let b = if y > 0 { pixels[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= 4 && y > 0 { pixels[(y - 1) * stride + x - 4] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(PngError::Corrupt("unknown filter type")),
+            };
+            pixels.push(row[x + 1].wrapping_add(predicted));
+        }
+    }
+    Ok(Image { width, height, pixels })
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+    push_u32(png, body.len() as u32);
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(body);
+    let crc = crc32(&png[start..]);
+    push_u32(png, crc);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+// Length and distance code tables from RFC 1951
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.bit_buffer |= value << self.bit_count;
+        self.bit_count += bits;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored most significant bit first, unlike everything else.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let mut reversed = 0;
+        for i in 0..bits {
+            reversed |= ((code >> i) & 1) << (bits - 1 - i);
+        }
+        self.write(reversed, bits);
+    }
+
+    fn write_literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+        self.write_literal(257 + code as u32);
+        self.write((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+        let code = DIST_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+        self.write_code(code as u32, 5);
+        self.write((distance - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bit_buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn zlib_compress(data: &[u8], row_length: usize) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: vec![0x78, 0x01], bit_buffer: 0, bit_count: 0 };
+    // A single final block with fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let match_length = |pos: usize, distance: usize| {
+        if distance > pos || distance > 32768 {
+            return 0;
+        }
+        let mut length = 0;
+        while length < 258 && pos + length < data.len() && data[pos + length] == data[pos + length - distance] {
+            length += 1;
+        }
+        length
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let best = [4, row_length].iter()
+            .map(|&distance| (match_length(pos, distance), distance))
+            .max_by_key(|&(length, _)| length)
+            .unwrap();
+        if best.0 >= 3 {
+            writer.write_match(best.0, best.1);
+            pos += best.0;
+        } else {
+            writer.write_literal(data[pos] as u32);
+            pos += 1;
+        }
+    }
+    writer.write_literal(256);
+    let mut bytes = writer.finish();
+    push_u32(&mut bytes, adler32(data));
+    bytes
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bit(&mut self) -> Result<u32, PngError> {
+        let byte = *self.data.get(self.pos).ok_or(PngError::Corrupt("unexpected end of image data"))?;
+        let value = (byte as u32 >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, PngError> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman decoding table: symbol counts per code length and the symbols sorted by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for i in 1..16 {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, PngError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bit()? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(PngError::Corrupt("bad Huffman code"))
+    }
+}
+
+fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, PngError> {
+    if data.len() < 6 || data[0] & 0x0f != 8 || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31) {
+        return Err(PngError::Corrupt("bad zlib header"));
+    }
+    let mut reader = BitReader { data: &data[2..], pos: 0, bit: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bit()? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let bytes = reader.data;
+                let pos = reader.pos;
+                if bytes.len() < pos + 4 {
+                    return Err(PngError::Corrupt("truncated stored block"));
+                }
+                let length = bytes[pos] as usize | (bytes[pos + 1] as usize) << 8;
+                if bytes.len() < pos + 4 + length {
+                    return Err(PngError::Corrupt("truncated stored block"));
+                }
+                out.extend_from_slice(&bytes[pos + 4..pos + 4 + length]);
+                reader.pos += 4 + length;
+            },
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (i, length) in lengths.iter_mut().enumerate() {
+                    *length = match i {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8,
+                    };
+                }
+                inflate_block(&mut reader, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            },
+            2 => {
+                let literal_count = reader.bits(5)? as usize + 257;
+                let distance_count = reader.bits(5)? as usize + 1;
+                let code_length_count = reader.bits(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+                    code_lengths[i] = reader.bits(3)? as u8;
+                }
+                let code_length_codes = Huffman::new(&code_lengths);
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let symbol = code_length_codes.decode(&mut reader)?;
+                    let (value, repeat) = match symbol {
+                        0..=15 => (symbol as u8, 1),
+                        16 => (*lengths.last().ok_or(PngError::Corrupt("repeat with no previous length"))?, 3 + reader.bits(2)?),
+                        17 => (0, 3 + reader.bits(3)?),
+                        _ => (0, 11 + reader.bits(7)?),
+                    };
+                    for _ in 0..repeat {
+                        lengths.push(value);
+                    }
+                }
+                if lengths.len() != literal_count + distance_count {
+                    return Err(PngError::Corrupt("code lengths overflow"));
+                }
+                let literals = Huffman::new(&lengths[..literal_count]);
+                let distances = Huffman::new(&lengths[literal_count..]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            },
+            _ => return Err(PngError::Corrupt("bad block type")),
+        }
+        if last {
+            break;
+        }
+    }
+    reader.align();
+    let rest = &reader.data[reader.pos..];
+    if rest.len() < 4 || read_u32(rest) != adler32(&out) {
+        return Err(PngError::Corrupt("image data checksum mismatch"));
+    }
+    Ok(out)
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), PngError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let code = symbol - 257;
+            if code >= LENGTH_BASE.len() {
+                return Err(PngError::Corrupt("bad length code"));
+            }
+            let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as u32)? as usize;
+            let code = distances.decode(reader)? as usize;
+            if code >= DIST_BASE.len() {
+                return Err(PngError::Corrupt("bad distance code"));
+            }
+            let distance = DIST_BASE[code] as usize + reader.bits(DIST_EXTRA[code] as u32)? as usize;
+            if distance > out.len() {
+                return Err(PngError::Corrupt("distance too far back"));
+            }
+            for _ in 0..length {
+                let byte = out[out.len() - distance];
+                out.push(byte);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut pixels = Vec::new();
+        for i in 0..(7 * 5) {
+            pixels.extend_from_slice(&[i as u8, 0, 200, 255]);
+        }
+        let image = decode(&encode(7, 5, &pixels)).unwrap();
+        assert_eq!((image.width, image.height), (7, 5));
+        assert_eq!(image.pixels, pixels);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        match decode(b"GIF89a") {
+            Err(PngError::NotPng) => {},
+            _ => panic!("Expected NotPng"),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use sdl2::event::Event;
+use sdl2::mouse::MouseButton;
+
+use constants::*;
+use game::Direction;
+
+/// What a press and release of the mouse, or a touch, amounted to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Gesture {
+    /// Released close to where it was pressed, at these logical coordinates.
+    Tap(i32, i32),
+    /// Dragged at least `SWIPE_DISTANCE` pixels, mostly in this direction.
+    Swipe(Direction),
+}
+
+/// The direction of a vector along its longer axis.
+pub fn direction(dx: i32, dy: i32) -> Direction {
+    if dx.abs() > dy.abs() {
+        if dx < 0 { Direction::Left } else { Direction::Right }
+    } else if dy < 0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+/// Follows the left mouse button to tell taps from swipes. Touches work the
+/// same way, since SDL turns them into mouse events too. The events are
+/// expected to be in logical coordinates, as `EventIterator` gives them.
+#[derive(Default)]
+pub struct Pointer {
+    pressed: Option<(i32, i32)>,
+}
+
+impl Pointer {
+    pub fn new() -> Pointer {
+        Pointer::default()
+    }
+
+    pub fn handle(&mut self, event: &Event) -> Option<Gesture> {
+        match *event {
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                self.pressed = Some((x, y));
+                None
+            },
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, x, y, .. } => {
+                let (start_x, start_y) = self.pressed.take()?;
+                let (dx, dy) = (x - start_x, y - start_y);
+                if dx.abs().max(dy.abs()) < SWIPE_DISTANCE {
+                    Some(Gesture::Tap(start_x, start_y))
+                } else {
+                    Some(Gesture::Swipe(direction(dx, dy)))
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::mouse::MouseState;
+
+    use super::*;
+
+    fn button(down: bool, x: i32, y: i32) -> Event {
+        let (timestamp, window_id, which, mouse_btn) = (0, 0, 0, MouseButton::Left);
+        if down {
+            Event::MouseButtonDown { timestamp, window_id, which, mouse_btn, x, y }
+        } else {
+            Event::MouseButtonUp { timestamp, window_id, which, mouse_btn, x, y }
+        }
+    }
+
+    #[test]
+    fn taps_and_swipes() {
+        let mut pointer = Pointer::new();
+        assert_eq!(pointer.handle(&button(true, 100, 100)), None);
+        assert_eq!(pointer.handle(&button(false, 102, 99)), Some(Gesture::Tap(100, 100)));
+        pointer.handle(&button(true, 100, 100));
+        assert_eq!(pointer.handle(&button(false, 90, 140)), Some(Gesture::Swipe(Direction::Down)));
+        pointer.handle(&button(true, 100, 100));
+        assert_eq!(pointer.handle(&button(false, 100 - SWIPE_DISTANCE, 100)), Some(Gesture::Swipe(Direction::Left)));
+        // A release without a press, like after coming back from a menu
+        assert_eq!(pointer.handle(&button(false, 0, 0)), None);
+        let motion = Event::MouseMotion { timestamp: 0, window_id: 0, which: 0, mousestate: MouseState::from_sdl_state(0), x: 0, y: 0, xrel: 0, yrel: 0 };
+        assert_eq!(pointer.handle(&motion), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+
+use sdl2::pixels::Color;
+
+use constants::*;
+use gif::{GifEncoder, PaletteMapper};
+use png::Image;
+use screenshot;
+
+/// Records presented frames into an animated GIF in `RECORDING_DIR`.
+///
+/// A frame is only written when the next one arrives (or the recording ends),
+/// as that's when we know how long it was shown.
+pub struct Recorder {
+    encoder: GifEncoder<BufWriter<File>>,
+    mapper: PaletteMapper,
+    path: PathBuf,
+    pending: Option<(Vec<u8>, u32)>,
+}
+
+impl Recorder {
+    /// Starts a recording. The palette should have every color the game draws with, so the recording comes out exact.
+    pub fn start(width: u32, height: u32, palette: &[Color]) -> io::Result<Recorder> {
+        fs::create_dir_all(RECORDING_DIR)?;
+        let mut path = PathBuf::from(RECORDING_DIR);
+        path.push(format!("mato-{}.gif", screenshot::timestamp()));
+        let file = BufWriter::new(File::create(&path)?);
+        let encoder = GifEncoder::new(file, width as u16, height as u16, palette)?;
+        Ok(Recorder {
+            encoder,
+            mapper: PaletteMapper::new(palette),
+            path,
+            pending: None,
+        })
+    }
+
+    /// Adds a frame that was presented at `time` milliseconds.
+    pub fn add_frame(&mut self, image: &Image, time: u32) -> io::Result<()> {
+        self.flush_pending(time)?;
+        self.pending = Some((self.mapper.map(&image.pixels), time));
+        Ok(())
+    }
+
+    pub fn finish(mut self, time: u32) -> io::Result<PathBuf> {
+        self.flush_pending(time)?;
+        self.encoder.finish()?;
+        Ok(self.path)
+    }
+
+    fn flush_pending(&mut self, time: u32) -> io::Result<()> {
+        if let Some((indices, shown_at)) = self.pending.take() {
+            // GIF delays are in hundredths of a second, and viewers treat very short ones oddly.
+            // A long pause, e.g. while the window was minimized, must not wrap around.
+            let delay = ::std::cmp::min(u16::MAX as u32, ::std::cmp::max(2, time.saturating_sub(shown_at) / 10));
+            self.encoder.add_frame(&indices, delay as u16)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+#![allow(dead_code)]
+
+use std::io::{self, Write};
+
+use sdl2::render::{WindowCanvas, TextureCreator, Texture, BlendMode};
+use sdl2::video::WindowContext;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+
+use text::GlyphAtlas;
+use png::Image;
+
+/// The drawing operations the game and its screens need.
+///
+/// Coordinates given to the drawing methods are relative to the current
+/// viewport, and drawing is clipped to it, the same way SDL does it. Colors
+/// that aren't fully opaque are blended over what is already there.
+pub trait Renderer {
+    fn set_viewport(&mut self, rect: Rect);
+
+    /// Clears the whole render target, regardless of the viewport.
+    fn clear(&mut self, color: Color);
+
+    fn fill_rect(&mut self, rect: Rect, color: Color);
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        for rect in rects {
+            self.fill_rect(*rect, color);
+        }
+    }
+
+    /// Draws glyphs from the atlas; each item is a (source, destination) pair like the ones `GlyphIterator` yields.
+    fn draw_glyphs(&mut self, atlas: &GlyphAtlas, glyphs: &mut dyn Iterator<Item=(Rect, Rect)>, color: Color);
+
+    /// Reads back the whole render target as RGBA. Call it before `present`,
+    /// as the contents of the target are undefined after presenting.
+    fn read_pixels(&mut self) -> Result<Image, String>;
+
+    fn present(&mut self);
+}
+
+/// How the logical resolution is placed in the window, see `SdlRenderer`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Letterbox {
+    scale: u32,
+    offset: (i32, i32),
+}
+
+impl Letterbox {
+    pub fn to_logical(self, x: i32, y: i32) -> (i32, i32) {
+        let scale = self.scale as i32;
+        ((x - self.offset.0).div_euclid(scale), (y - self.offset.1).div_euclid(scale))
+    }
+}
+
+/// Draws with an SDL canvas. Everything is drawn at a fixed logical
+/// resolution, scaled up by the largest integer factor that fits the window
+/// and centered with black bars around it.
+pub struct SdlRenderer<'a> {
+    canvas: WindowCanvas,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    glyph_textures: Vec<(usize, Texture<'a>)>,
+    logical_size: (u32, u32),
+    scale: u32,
+    offset: (i32, i32),
+}
+
+impl<'a> SdlRenderer<'a> {
+    pub fn new(mut canvas: WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>, logical_width: u32, logical_height: u32) -> SdlRenderer<'a> {
+        canvas.set_blend_mode(BlendMode::Blend);
+        let mut renderer = SdlRenderer {
+            canvas,
+            texture_creator,
+            glyph_textures: Vec::new(),
+            logical_size: (logical_width, logical_height),
+            scale: 1,
+            offset: (0, 0),
+        };
+        renderer.update_layout();
+        renderer
+    }
+
+    /// Recalculates the scale and the letterbox offset from the current output size.
+    fn update_layout(&mut self) {
+        let (output_width, output_height) = self.canvas.output_size().expect("Getting output size");
+        let (width, height) = self.logical_size;
+        self.scale = ::std::cmp::max(1, ::std::cmp::min(output_width / width, output_height / height));
+        self.offset = (
+            (output_width as i32 - (width * self.scale) as i32) / 2,
+            (output_height as i32 - (height * self.scale) as i32) / 2,
+        );
+    }
+
+    fn scaled(&self, rect: Rect) -> Rect {
+        let scale = self.scale as i32;
+        Rect::new(rect.x() * scale, rect.y() * scale, rect.width() * self.scale, rect.height() * self.scale)
+    }
+
+    /// Converts window coordinates, like the ones in mouse events, to logical coordinates.
+    pub fn to_logical(&self, x: i32, y: i32) -> (i32, i32) {
+        self.letterbox().to_logical(x, y)
+    }
+
+    /// The current scale and offset, for converting coordinates without holding on to the renderer.
+    pub fn letterbox(&self) -> Letterbox {
+        Letterbox { scale: self.scale, offset: self.offset }
+    }
+
+    pub fn canvas(&self) -> &WindowCanvas {
+        &self.canvas
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut WindowCanvas {
+        &mut self.canvas
+    }
+
+    fn glyph_texture_index(&mut self, atlas: &GlyphAtlas) -> usize {
+        if let Some(i) = self.glyph_textures.iter().position(|&(id, _)| id == atlas.id()) {
+            return i;
+        }
+        let (width, height) = atlas.size();
+        let mut texture = self.texture_creator
+            .create_texture_static(PixelFormatEnum::ABGR8888, width, height)
+            .expect("Could not create texture");
+        texture.update(None, &atlas.rgba_pixels()[..], (width * 4) as usize).expect("Could not set texture data");
+        texture.set_blend_mode(BlendMode::Blend);
+        self.glyph_textures.push((atlas.id(), texture));
+        self.glyph_textures.len() - 1
+    }
+}
+
+impl<'a> Renderer for SdlRenderer<'a> {
+    fn set_viewport(&mut self, rect: Rect) {
+        let mut physical = self.scaled(rect);
+        physical.offset(self.offset.0, self.offset.1);
+        self.canvas.set_viewport(physical);
+    }
+
+    /// Clears the whole window, so the letterbox bars get the color too.
+    /// As this starts every frame, it is also where a changed window size is picked up.
+    fn clear(&mut self, color: Color) {
+        self.update_layout();
+        self.canvas.set_viewport(None);
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = self.scaled(rect);
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(rect).expect("Could not fill rect");
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect], color: Color) {
+        let rects: Vec<Rect> = rects.iter().map(|&rect| self.scaled(rect)).collect();
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rects(&rects).expect("Could not fill rects");
+    }
+
+    fn draw_glyphs(&mut self, atlas: &GlyphAtlas, glyphs: &mut dyn Iterator<Item=(Rect, Rect)>, color: Color) {
+        let i = self.glyph_texture_index(atlas);
+        let texture = &mut self.glyph_textures[i].1;
+        texture.set_color_mod(color.r, color.g, color.b);
+        texture.set_alpha_mod(color.a);
+        let scale = self.scale as i32;
+        for (src, dst) in glyphs {
+            let dst = Rect::new(dst.x() * scale, dst.y() * scale, dst.width() * self.scale, dst.height() * self.scale);
+            self.canvas.copy(texture, src, dst).expect("Could not draw glyph");
+        }
+    }
+
+    /// Reads back the logical image, without the letterbox bars and scaled back down.
+    fn read_pixels(&mut self) -> Result<Image, String> {
+        let (width, height) = self.logical_size;
+        let scale = self.scale;
+        let area = Rect::new(self.offset.0, self.offset.1, width * scale, height * scale);
+        self.canvas.set_viewport(None);
+        // ABGR8888 is R, G, B, A in memory on little endian machines
+        let scaled = self.canvas.read_pixels(area, PixelFormatEnum::ABGR8888)?;
+        let scaled_stride = (width * scale * 4) as usize;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let row = (y * scale) as usize * scaled_stride;
+            for x in 0..width {
+                let i = row + (x * scale * 4) as usize;
+                pixels.extend_from_slice(&scaled[i..i + 4]);
+            }
+        }
+        Ok(Image { width, height, pixels })
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+}
+
+/// Renders into an RGBA buffer in memory. Useful for tests and anything else
+/// that wants the pixels without a window.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    viewport: Rect,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            viewport: Rect::new(0, 0, width, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Pixels as RGBA, row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = ((y * self.width + x) * 4) as usize;
+        Color::RGBA(self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3])
+    }
+
+    /// Blends the way SDL's `BlendMode::Blend` does.
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color) {
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        let alpha = color.a as u32;
+        let blend = |src: u8, dst: u8| ((src as u32 * alpha + dst as u32 * (255 - alpha)) / 255) as u8;
+        self.pixels[i] = blend(color.r, self.pixels[i]);
+        self.pixels[i + 1] = blend(color.g, self.pixels[i + 1]);
+        self.pixels[i + 2] = blend(color.b, self.pixels[i + 2]);
+        self.pixels[i + 3] = (alpha + self.pixels[i + 3] as u32 * (255 - alpha) / 255) as u8;
+    }
+
+    /// Translates a viewport-relative rect to buffer coordinates and clips it.
+    /// Returns (left, top, right, bottom), right and bottom exclusive.
+    fn clip(&self, rect: Rect) -> (i32, i32, i32, i32) {
+        let vp = self.viewport;
+        let left = vp.x().max(0);
+        let top = vp.y().max(0);
+        let right = (vp.x() + vp.width() as i32).min(self.width as i32);
+        let bottom = (vp.y() + vp.height() as i32).min(self.height as i32);
+        let x = rect.x() + vp.x();
+        let y = rect.y() + vp.y();
+        (
+            x.max(left),
+            y.max(top),
+            (x + rect.width() as i32).min(right),
+            (y + rect.height() as i32).min(bottom),
+        )
+    }
+}
+
+impl Renderer for Framebuffer {
+    fn set_viewport(&mut self, rect: Rect) {
+        self.viewport = rect;
+    }
+
+    fn clear(&mut self, color: Color) {
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel[0] = color.r;
+            pixel[1] = color.g;
+            pixel[2] = color.b;
+            pixel[3] = color.a;
+        }
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let (left, top, right, bottom) = self.clip(rect);
+        for y in top..bottom {
+            for x in left..right {
+                self.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    fn draw_glyphs(&mut self, atlas: &GlyphAtlas, glyphs: &mut dyn Iterator<Item=(Rect, Rect)>, color: Color) {
+        for (src, dst) in glyphs {
+            let (left, top, right, bottom) = self.clip(dst);
+            let origin_x = dst.x() + self.viewport.x();
+            let origin_y = dst.y() + self.viewport.y();
+            for y in top..bottom {
+                let src_y = src.y() as u32 + (y - origin_y) as u32 * src.height() / dst.height();
+                for x in left..right {
+                    let src_x = src.x() as u32 + (x - origin_x) as u32 * src.width() / dst.width();
+                    if atlas.pixel(src_x, src_y) {
+                        self.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_pixels(&mut self) -> Result<Image, String> {
+        Ok(Image { width: self.width, height: self.height, pixels: self.pixels.clone() })
+    }
+
+    fn present(&mut self) {}
+}
+
+/// Draws into a `Framebuffer` and shows it on a terminal with 24-bit ANSI
+/// colors, using half block characters so that one character cell covers
+/// two samples stacked vertically.
+pub struct TerminalRenderer<W: Write> {
+    framebuffer: Framebuffer,
+    out: W,
+    sample_size: u32,
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    /// `sample_size` is the size of the square of pixels one half of a character cell stands for.
+    pub fn new(width: u32, height: u32, sample_size: u32, out: W) -> TerminalRenderer<W> {
+        TerminalRenderer {
+            framebuffer: Framebuffer::new(width, height),
+            out,
+            sample_size,
+        }
+    }
+
+    fn sample(&self, column: u32, row: u32) -> Color {
+        let x = column * self.sample_size + self.sample_size / 2;
+        let y = row * self.sample_size + self.sample_size / 2;
+        if x < self.framebuffer.width() && y < self.framebuffer.height() {
+            self.framebuffer.pixel(x, y)
+        } else {
+            Color::RGB(0, 0, 0)
+        }
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let columns = self.framebuffer.width() / self.sample_size;
+        let rows = (self.framebuffer.height() / self.sample_size).div_ceil(2);
+        let mut frame = String::from("\x1b[H");
+        for row in 0..rows {
+            for column in 0..columns {
+                let top = self.sample(column, row * 2);
+                let bottom = self.sample(column, row * 2 + 1);
+                frame.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top.r, top.g, top.b, bottom.r, bottom.g, bottom.b));
+            }
+            frame.push_str("\x1b[0m\r\n");
+        }
+        self.out.write_all(frame.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+    fn set_viewport(&mut self, rect: Rect) {
+        self.framebuffer.set_viewport(rect);
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.framebuffer.clear(color);
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        self.framebuffer.fill_rect(rect, color);
+    }
+
+    fn draw_glyphs(&mut self, atlas: &GlyphAtlas, glyphs: &mut dyn Iterator<Item=(Rect, Rect)>, color: Color) {
+        self.framebuffer.draw_glyphs(atlas, glyphs, color);
+    }
+
+    fn read_pixels(&mut self) -> Result<Image, String> {
+        self.framebuffer.read_pixels()
+    }
+
+    fn present(&mut self) {
+        self.write_frame().expect("Could not write to terminal");
+    }
+}
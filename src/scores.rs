@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use constants::*;
+
+/// The best score of each game mode, kept in `HIGH_SCORE_FILE` as lines of
+/// `<mode name> <score>`. Scores are points or milliseconds, depending on
+/// the mode.
+pub struct HighScores {
+    entries: Vec<(String, u32)>,
+}
+
+impl HighScores {
+    /// Loads the high scores; a missing or broken file means no scores yet.
+    pub fn load() -> HighScores {
+        let mut text = String::new();
+        match File::open(HIGH_SCORE_FILE).and_then(|mut f| f.read_to_string(&mut text)) {
+            Ok(_) => HighScores::parse(&text),
+            Err(_) => HighScores { entries: Vec::new() },
+        }
+    }
+
+    fn parse(text: &str) -> HighScores {
+        let entries = text.lines().filter_map(|line| {
+            let mut parts = line.trim().rsplitn(2, ' ');
+            let points = parts.next()?.parse().ok()?;
+            let mode = parts.next()?.trim();
+            Some((mode.to_string(), points))
+        }).collect();
+        HighScores { entries }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut f = File::create(HIGH_SCORE_FILE)?;
+        for &(ref mode, points) in &self.entries {
+            writeln!(f, "{} {}", mode, points)?;
+        }
+        Ok(())
+    }
+
+    pub fn best(&self, mode: &str) -> Option<u32> {
+        self.entries.iter().find(|entry| entry.0 == mode).map(|entry| entry.1)
+    }
+
+    /// Returns true if `score` is a new high score for the mode.
+    pub fn record(&mut self, mode: &str, score: u32, lower_is_better: bool) -> bool {
+        let better = |best: u32| if lower_is_better { score < best } else { score > best };
+        match self.entries.iter_mut().find(|entry| entry.0 == mode) {
+            Some(entry) if better(entry.1) => entry.1 = score,
+            Some(_) => return false,
+            None => self.entries.push((mode.to_string(), score)),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_record() {
+        let mut scores = HighScores::parse("Classic 120\nTime Attack 45\nbroken line\n");
+        assert_eq!(scores.best("Classic"), Some(120));
+        assert_eq!(scores.best("Time Attack"), Some(45));
+        assert_eq!(scores.best("Survival"), None);
+        assert!(!scores.record("Classic", 100, false));
+        assert!(scores.record("Classic", 130, false));
+        assert!(scores.record("Survival", 1, false));
+        assert_eq!(scores.best("Classic"), Some(130));
+        assert_eq!(scores.best("Survival"), Some(1));
+        assert!(scores.record("Sprint", 30000, true));
+        assert!(!scores.record("Sprint", 31000, true));
+        assert!(scores.record("Sprint", 29000, true));
+    }
+}
@@ -0,0 +1,148 @@
+use constants::*;
+use game::AppleKind;
+
+/// What the scoring rules get to know about an apple being eaten.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Meal {
+    pub kind: AppleKind,
+    pub step_duration: u32,
+    /// How many apples in a row, before this one, were eaten quickly enough
+    /// to keep a combo going.
+    pub combo: u32,
+    /// Length of the worm before eating.
+    pub length: usize,
+    /// Steps taken since the previous apple, or since the start.
+    pub steps: u32,
+    /// The fewest steps it could have taken.
+    pub shortest: u32,
+}
+
+/// One way of earning or losing points for an apple. The rules of a game are
+/// a list of these; each one's share of the score is kept separately for the
+/// breakdown at the end. Poison apples are worth nothing under any rule.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScoreRule {
+    /// Points per kind of apple at the starting speed, going up as the
+    /// steps get shorter.
+    Base { normal: u32, golden: u32, slow: u32 },
+    /// `bonus` points for each apple in the current combo, counting at most
+    /// `max` of them. An apple eaten within `window` steps of the previous
+    /// one keeps the combo going.
+    Combo { window: u32, bonus: u32, max: u32 },
+    /// Points for each segment of the worm.
+    Length { per_segment: u32 },
+    /// Points off for each step more than `allowance` on top of the
+    /// shortest way to the apple.
+    Detour { allowance: u32, per_step: u32 },
+}
+
+impl ScoreRule {
+    pub fn defaults() -> Vec<ScoreRule> {
+        vec![
+            ScoreRule::Base { normal: 10, golden: 10 * GOLDEN_APPLE_MULTIPLIER, slow: 10 },
+            ScoreRule::Combo { window: 10, bonus: 5, max: 5 },
+            ScoreRule::Length { per_segment: 1 },
+            ScoreRule::Detour { allowance: 10, per_step: 1 },
+        ]
+    }
+
+    /// Name for the score breakdown.
+    pub fn name(self) -> &'static str {
+        match self {
+            ScoreRule::Base { .. } => "APPLES",
+            ScoreRule::Combo { .. } => "COMBOS",
+            ScoreRule::Length { .. } => "LENGTH",
+            ScoreRule::Detour { .. } => "DETOURS",
+        }
+    }
+
+    /// This rule's points for `meal`, negative for a penalty.
+    pub fn points(self, meal: &Meal) -> i32 {
+        if meal.kind == AppleKind::Poison {
+            return 0;
+        }
+        match self {
+            ScoreRule::Base { normal, golden, slow } => {
+                let base = match meal.kind {
+                    AppleKind::Normal => normal,
+                    AppleKind::Golden => golden,
+                    AppleKind::Slow => slow,
+                    AppleKind::Poison => 0,
+                };
+                (base * INITIAL_STEP_DURATION / meal.step_duration) as i32
+            },
+            ScoreRule::Combo { bonus, max, .. } => (bonus * meal.combo.min(max)) as i32,
+            ScoreRule::Length { per_segment } => (per_segment * meal.length as u32) as i32,
+            ScoreRule::Detour { allowance, per_step } => {
+                let extra = meal.steps.saturating_sub(meal.shortest + allowance);
+                -((extra * per_step) as i32)
+            },
+        }
+    }
+
+    /// Steps within which the next apple keeps a combo going, if this is a combo rule.
+    pub fn combo_window(self) -> Option<u32> {
+        match self {
+            ScoreRule::Combo { window, .. } => Some(window),
+            _ => None,
+        }
+    }
+}
+
+/// Points earned under each heading during a game, in the order they first
+/// came up.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ScoreBreakdown {
+    pub lines: Vec<(&'static str, i32)>,
+}
+
+impl ScoreBreakdown {
+    pub fn add(&mut self, name: &'static str, points: i32) {
+        match self.lines.iter_mut().find(|line| line.0 == name) {
+            Some(line) => line.1 += points,
+            None => self.lines.push((name, points)),
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.lines.iter().map(|line| line.1).sum()
+    }
+}
+
+/// Scores `meal` with `rules`, adding each rule's share to `breakdown`.
+/// Penalties only take away what the rules before them gave, so an apple is
+/// never worth less than nothing. Returns the apple's points.
+pub fn score(rules: &[ScoreRule], meal: &Meal, breakdown: &mut ScoreBreakdown) -> u32 {
+    let mut total = 0;
+    for &rule in rules {
+        let points = rule.points(meal).max(-total);
+        total += points;
+        if points != 0 {
+            breakdown.add(rule.name(), points);
+        }
+    }
+    total as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_add_up() {
+        let meal = Meal { kind: AppleKind::Normal, step_duration: INITIAL_STEP_DURATION, combo: 7, length: 4, steps: 30, shortest: 5 };
+        let mut breakdown = ScoreBreakdown::default();
+        let points = score(&ScoreRule::defaults(), &meal, &mut breakdown);
+        assert_eq!(breakdown.lines, vec![("APPLES", 10), ("COMBOS", 25), ("LENGTH", 4), ("DETOURS", -15)]);
+        assert_eq!(points, 24);
+        assert_eq!(breakdown.total(), 24);
+
+        // The penalty can't take more than the apple was worth
+        let meal = Meal { combo: 0, steps: 100, ..meal };
+        assert_eq!(score(&ScoreRule::defaults(), &meal, &mut breakdown), 0);
+        assert_eq!(breakdown.lines, vec![("APPLES", 20), ("COMBOS", 25), ("LENGTH", 8), ("DETOURS", -29)]);
+
+        let poison = Meal { kind: AppleKind::Poison, ..meal };
+        assert_eq!(score(&ScoreRule::defaults(), &poison, &mut breakdown), 0);
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use constants::*;
+use png::{self, Image};
+
+/// Saves the image as a PNG named after the current time into `SCREENSHOT_DIR`.
+pub fn save(image: &Image) -> io::Result<PathBuf> {
+    fs::create_dir_all(SCREENSHOT_DIR)?;
+    let mut path = PathBuf::from(SCREENSHOT_DIR);
+    path.push(format!("mato-{}.png", timestamp()));
+    let mut file = File::create(&path)?;
+    file.write_all(&png::encode(image.width, image.height, &image.pixels))?;
+    Ok(path)
+}
+
+/// Current UTC time formatted as YYYYMMDD-HHMMSS-mmm, which sorts nicely as a file name.
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year, month, day,
+        time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60,
+        now.subsec_millis())
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date.
+/// This is Howard Hinnant's `civil_from_days` algorithm.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let day_of_era = (z - era * 146097) as u32;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era as i64 + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::civil_from_days;
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,92 @@
+use constants::*;
+
+/// How the time between the worm's steps changes during a game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpeedCurve {
+    /// Faster by `STEP_DURATION_DECREMENT` with each apple.
+    Linear,
+    /// The starting speed for the whole game.
+    Constant,
+    /// `EXPONENTIAL_SPEED_FACTOR` times the previous step duration with each
+    /// apple, so that it speeds up quickly at first and then levels off.
+    Exponential,
+    /// Faster by `STEPPED_SPEED_DECREMENT` every `STEPPED_SPEED_APPLES` apples.
+    Stepped,
+    /// Faster by `STEP_DURATION_DECREMENT` every `TIMED_SPEED_INTERVAL`
+    /// milliseconds, whether apples get eaten or not.
+    TimeBased,
+}
+
+impl SpeedCurve {
+    pub fn all() -> Vec<SpeedCurve> {
+        use self::SpeedCurve::*;
+        vec![Linear, Constant, Exponential, Stepped, TimeBased]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpeedCurve::Linear => "Linear",
+            SpeedCurve::Constant => "Constant",
+            SpeedCurve::Exponential => "Exponential",
+            SpeedCurve::Stepped => "Stepped",
+            SpeedCurve::TimeBased => "Timed",
+        }
+    }
+
+    /// Milliseconds between steps after eating `apples` apples in `elapsed`
+    /// milliseconds. Never below `MIN_STEP_DURATION`.
+    pub fn step_duration(self, apples: u32, elapsed: u32) -> u32 {
+        let duration = match self {
+            SpeedCurve::Linear => INITIAL_STEP_DURATION.saturating_sub(apples * STEP_DURATION_DECREMENT),
+            SpeedCurve::Constant => INITIAL_STEP_DURATION,
+            SpeedCurve::Exponential => (INITIAL_STEP_DURATION as f64 * EXPONENTIAL_SPEED_FACTOR.powi(apples as i32)) as u32,
+            SpeedCurve::Stepped => INITIAL_STEP_DURATION.saturating_sub(apples / STEPPED_SPEED_APPLES * STEPPED_SPEED_DECREMENT),
+            SpeedCurve::TimeBased => INITIAL_STEP_DURATION.saturating_sub(elapsed / TIMED_SPEED_INTERVAL * STEP_DURATION_DECREMENT),
+        };
+        duration.max(MIN_STEP_DURATION)
+    }
+
+    /// Speed as a level number starting from 1, going up by one each time
+    /// `step_duration` gets shorter on the way to `apples` and `elapsed`.
+    pub fn level(self, apples: u32, elapsed: u32) -> u32 {
+        let speed_ups = match self {
+            SpeedCurve::TimeBased => (0..elapsed / TIMED_SPEED_INTERVAL)
+                .take_while(|&i| self.step_duration(0, i * TIMED_SPEED_INTERVAL) > MIN_STEP_DURATION)
+                .count(),
+            _ => (0..apples)
+                .take_while(|&i| self.step_duration(i, 0) > MIN_STEP_DURATION)
+                .filter(|&i| self.step_duration(i + 1, 0) < self.step_duration(i, 0))
+                .count(),
+        };
+        speed_ups as u32 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        assert_eq!(SpeedCurve::Linear.step_duration(3, 0), INITIAL_STEP_DURATION - 3 * STEP_DURATION_DECREMENT);
+        assert_eq!(SpeedCurve::Linear.step_duration(1000, 0), MIN_STEP_DURATION);
+        assert_eq!(SpeedCurve::Constant.step_duration(1000, 100000), INITIAL_STEP_DURATION);
+        assert_eq!(SpeedCurve::Exponential.step_duration(1, 0), (INITIAL_STEP_DURATION as f64 * EXPONENTIAL_SPEED_FACTOR) as u32);
+        assert_eq!(SpeedCurve::Stepped.step_duration(STEPPED_SPEED_APPLES - 1, 0), INITIAL_STEP_DURATION);
+        assert_eq!(SpeedCurve::Stepped.step_duration(STEPPED_SPEED_APPLES, 0), INITIAL_STEP_DURATION - STEPPED_SPEED_DECREMENT);
+        assert_eq!(SpeedCurve::TimeBased.step_duration(10, TIMED_SPEED_INTERVAL - 1), INITIAL_STEP_DURATION);
+        assert_eq!(SpeedCurve::TimeBased.step_duration(0, TIMED_SPEED_INTERVAL * 2), INITIAL_STEP_DURATION - 2 * STEP_DURATION_DECREMENT);
+
+        assert_eq!(SpeedCurve::Linear.level(3, 0), 4);
+        assert_eq!(SpeedCurve::Constant.level(1000, 100000), 1);
+        assert_eq!(SpeedCurve::Exponential.level(0, 0), 1);
+        assert_eq!(SpeedCurve::Exponential.level(2, 0), 3);
+        assert_eq!(SpeedCurve::Stepped.level(STEPPED_SPEED_APPLES - 1, 0), 1);
+        assert_eq!(SpeedCurve::Stepped.level(STEPPED_SPEED_APPLES * 2, 0), 3);
+        assert_eq!(SpeedCurve::TimeBased.level(10, TIMED_SPEED_INTERVAL * 2), 3);
+        // Levels stop going up once the steps can't get any shorter
+        assert_eq!(SpeedCurve::Linear.level(1000, 0), SpeedCurve::Linear.level(2000, 0));
+        assert_eq!(SpeedCurve::Exponential.level(1000, 0), SpeedCurve::Exponential.level(2000, 0));
+        assert_eq!(SpeedCurve::TimeBased.level(0, TIMED_SPEED_INTERVAL * 1000), SpeedCurve::TimeBased.level(0, TIMED_SPEED_INTERVAL * 2000));
+    }
+}
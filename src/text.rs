@@ -2,15 +2,24 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use sdl2::rect::Rect;
-use sdl2::pixels::PixelFormatEnum;
-use sdl2::render::{TextureCreator,Texture,BlendMode};
 
 
 pub const GLYPH_COUNT: u32 = 256;
 
 const GLYPH_WIDTH: u32 = 8;
 const GLYPHS: &[u8] = include_bytes!("../COMPUTER.F14");
+/// Pairs of the built-in font that look too far apart with the usual gap
+/// between them, with the pixels to move the second one by.
+const KERNING: &[(&[u8; 2], i32)] = &[
+    (b"AT", -1), (b"TA", -1), (b"AV", -1), (b"VA", -1), (b"AW", -1), (b"WA", -1),
+    (b"AY", -1), (b"YA", -1), (b"LT", -1), (b"LV", -1), (b"LY", -1), (b"TO", -1),
+    (b"OT", -1), (b"Ta", -1), (b"Te", -1), (b"To", -1), (b"Yo", -1), (b"P.", -1),
+];
+
+static NEXT_ATLAS_ID: AtomicUsize = AtomicUsize::new(0);
 
 pub trait StrAsBytes {
     fn as_bytes(&self) -> &[u8];
@@ -28,51 +37,161 @@ impl StrAsBytes for [u8] {
     }
 }
 
-pub struct GlyphAtlas<'a> {
-    texture: Texture<'a>,
+/// Bitmap font laid out as a single column of glyphs, one glyph below another.
+/// Fonts other than the built-in one are loaded with the `font` module.
+///
+/// Text is proportional by default: each glyph takes only the columns of its
+/// span, see `set_span`, followed by a one pixel gap, and kerning pairs move
+/// glyphs closer together or further apart. `GlyphIterator::monospace` lays
+/// text out in whole cells instead, for numbers that shouldn't jump around.
+///
+/// The atlas only holds the pixel data; renderers turn it into whatever they
+/// need (an SDL texture, for example) and can use `id` to cache the result.
+pub struct GlyphAtlas {
+    id: usize,
+    pixels: Vec<bool>,
     glyph_height: u32,
     glyph_width: u32,
+    glyph_count: u32,
+    /// The first column and the number of columns of each glyph
+    spans: Vec<(u32, u32)>,
+    kerning: Vec<(u8, u8, i32)>,
+}
+
+/// How `GlyphAtlas::text_width` measures text, see `GlyphAtlas`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spacing {
+    Proportional,
+    Monospace,
 }
 
-impl<'a> GlyphAtlas<'a> {
-    pub fn new<T>(texture_creator: &TextureCreator<T>) -> GlyphAtlas {
-        let atlas_width = GLYPH_WIDTH;
-        let atlas_height = GLYPHS.len() as u32;
-        let glyph_height = GLYPHS.len() as u32 / GLYPH_COUNT;
-        let mut buffer: Vec<u8> = Vec::with_capacity((atlas_width * atlas_height * 4) as usize);
-        let mut atlas = texture_creator.create_texture_static(PixelFormatEnum::ABGR8888, atlas_width, atlas_height).expect("Could not create texture");
-        for byte in GLYPHS {
-            for i in 0..8 {
-                let value = if byte & (0b1000_0000 >> i) != 0 {
-                    255
-                } else {
-                    0
-                };
-                buffer.push(value);
-                buffer.push(value);
-                buffer.push(value);
-                buffer.push(value);
+impl GlyphAtlas {
+    /// The built-in font.
+    pub fn new() -> GlyphAtlas {
+        let mut atlas = GlyphAtlas::from_bitmap(GLYPHS, GLYPH_WIDTH, GLYPH_COUNT);
+        for &(pair, offset) in KERNING {
+            atlas.add_kerning(pair[0], pair[1], offset);
+        }
+        atlas
+    }
+
+    /// Builds an atlas from 1-bit glyphs stored one after another, row by row,
+    /// with each row padded to whole bytes and the leftmost pixel in the most
+    /// significant bit. This is the layout of raw DOS font files, and the
+    /// glyph height is whatever is left over from the width and the count.
+    /// Each glyph's span is trimmed to the columns that have pixels set; blank
+    /// glyphs, like the space, get half the width.
+    pub fn from_bitmap(bits: &[u8], glyph_width: u32, glyph_count: u32) -> GlyphAtlas {
+        let bytes_per_row = glyph_width.div_ceil(8) as usize;
+        let glyph_height = bits.len() as u32 / bytes_per_row as u32 / glyph_count;
+        let mut pixels = Vec::with_capacity((glyph_width * glyph_height * glyph_count) as usize);
+        for row in bits.chunks(bytes_per_row).take((glyph_height * glyph_count) as usize) {
+            for x in 0..glyph_width as usize {
+                pixels.push(row[x / 8] & (0b1000_0000 >> (x % 8)) != 0);
             }
         }
-        atlas.update(None, &buffer[..], (atlas_width * 4) as usize).expect("Could not set texture data");
-        atlas.set_blend_mode(BlendMode::Blend);
-        GlyphAtlas {
-            texture: atlas,
-            glyph_width: GLYPH_WIDTH,
+        let mut atlas = GlyphAtlas {
+            id: NEXT_ATLAS_ID.fetch_add(1, Ordering::Relaxed),
+            pixels,
+            glyph_width,
             glyph_height,
+            glyph_count,
+            spans: Vec::new(),
+            kerning: Vec::new(),
+        };
+        atlas.spans = (0..glyph_count).map(|glyph| {
+            let top = glyph * glyph_height;
+            let inked = |x: &u32| (top..top + glyph_height).any(|y| atlas.pixel(*x, y));
+            match ((0..glyph_width).find(inked), (0..glyph_width).rev().find(inked)) {
+                (Some(left), Some(right)) => (left, right - left + 1),
+                _ => (0, (glyph_width / 2).max(1)),
+            }
+        }).collect();
+        atlas
+    }
+
+    /// Sets the columns a glyph takes in proportional text, for fonts that
+    /// know better than trimming, like BDF fonts with their advance widths.
+    pub fn set_span(&mut self, glyph: u8, left: u32, width: u32) {
+        let index = self.glyph_index(glyph) as usize;
+        let left = left.min(self.glyph_width - 1);
+        self.spans[index] = (left, width.clamp(1, self.glyph_width - left));
+    }
+
+    /// Moves `second` by `offset` pixels, at scale 1, when it follows `first`
+    /// in proportional text.
+    pub fn add_kerning(&mut self, first: u8, second: u8, offset: i32) {
+        self.kerning.retain(|&(a, b, _)| (a, b) != (first, second));
+        self.kerning.push((first, second, offset));
+    }
+
+    fn kerning(&self, first: u8, second: u8) -> i32 {
+        self.kerning.iter().find(|&&(a, b, _)| (a, b) == (first, second)).map_or(0, |pair| pair.2)
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn glyph_height(&self) -> u32 {
+        self.glyph_height
+    }
+
+    pub fn glyph_width(&self) -> u32 {
+        self.glyph_width
+    }
+
+    pub fn glyph_count(&self) -> u32 {
+        self.glyph_count
+    }
+
+    /// Size of the whole atlas in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.glyph_width, self.pixels.len() as u32 / self.glyph_width)
+    }
+
+    /// Whether the atlas pixel at (x, y) is set. Coordinates are atlas coordinates, like the ones in `glyph_rect`.
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        let (width, height) = self.size();
+        x < width && y < height && self.pixels[(y * width + x) as usize]
+    }
+
+    /// Atlas as white RGBA pixels with the alpha channel carrying the glyph shapes.
+    pub fn rgba_pixels(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.pixels.len() * 4);
+        for &set in &self.pixels {
+            let value = if set { 255 } else { 0 };
+            buffer.push(value);
+            buffer.push(value);
+            buffer.push(value);
+            buffer.push(value);
         }
+        buffer
     }
 
-    pub fn glyph_rect(&self, glyph: u8) -> Rect {
-        Rect::new(0, self.glyph_height as i32 * glyph as i32, self.glyph_width, self.glyph_height)
+    /// Fonts with fewer than 256 glyphs show the missing ones as a question
+    /// mark, if they have one.
+    fn glyph_index(&self, glyph: u8) -> u32 {
+        if (glyph as u32) < self.glyph_count {
+            glyph as u32
+        } else if (b'?' as u32) < self.glyph_count {
+            b'?' as u32
+        } else {
+            0
+        }
     }
 
-    pub fn texture(&self) -> &Texture {
-        &self.texture
+    /// Where the glyph's whole cell is in the atlas.
+    pub fn glyph_rect(&self, glyph: u8) -> Rect {
+        let y = self.glyph_height as i32 * self.glyph_index(glyph) as i32;
+        Rect::new(0, y, self.glyph_width, self.glyph_height)
     }
 
-    pub fn set_render_color(&mut self, r: u8, g: u8, b: u8) {
-        self.texture.set_color_mod(r, g, b)
+    /// Where the glyph's span is in the atlas.
+    pub fn span_rect(&self, glyph: u8) -> Rect {
+        let (left, width) = self.spans[self.glyph_index(glyph) as usize];
+        let cell = self.glyph_rect(glyph);
+        Rect::new(left as i32, cell.y(), width, self.glyph_height)
     }
 
     /*pub fn text_rects(&'a self, text: &'a str, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
@@ -85,38 +204,53 @@ impl<'a> GlyphAtlas<'a> {
         }*/
     }*/
 
-    pub fn text_rects<T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
+    pub fn text_rects<'a, T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
         GlyphIterator {
             atlas: self,
             chars: text.as_bytes(),
             scale,
-            position
+            position,
+            monospace: false,
+            previous: None,
         }
     }
 
-    pub fn text_rects_centered<T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
-        let x = position.0 - text_width(text, scale) / 2;
+    pub fn text_rects_centered<'a, T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
+        let x = position.0 - self.text_width(text, scale, Spacing::Proportional) / 2;
         let y = position.1 - (self.glyph_height as i32 * scale as i32 / 2);
         self.text_rects(text, (x, y), scale)
     }
 
-    pub fn text_rects_right_aligned<T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
-        let x = position.0 - text_width(text, scale);
+    pub fn text_rects_right_aligned<'a, T: StrAsBytes + ?Sized>(&'a self, text: &'a T, position: (i32, i32), scale: u32) -> GlyphIterator<'a> {
+        let x = position.0 - self.text_width(text, scale, Spacing::Proportional);
         let y = position.1;
         self.text_rects(text, (x, y), scale)
     }
 
-}
-
-fn text_width<T: StrAsBytes + ?Sized>(text: &T, scale: u32) -> i32 {
-    text.as_bytes().len() as i32 * GLYPH_WIDTH as i32 * scale as i32 + (text.as_bytes().len() - 1) as i32 * scale as i32
+    /// Width of the text in pixels, from the left edge of the first glyph to
+    /// the right edge of the last one.
+    pub fn text_width<T: StrAsBytes + ?Sized>(&self, text: &T, scale: u32, spacing: Spacing) -> i32 {
+        let glyphs = self.text_rects(text, (0, 0), scale);
+        let glyphs = if spacing == Spacing::Monospace { glyphs.monospace() } else { glyphs };
+        glyphs.last().map_or(0, |(_, dst)| dst.right())
+    }
 }
 
 pub struct GlyphIterator<'a> {
-    atlas: &'a GlyphAtlas<'a>,
+    atlas: &'a GlyphAtlas,
     chars: &'a [u8],
     scale: u32,
     position: (i32, i32),
+    monospace: bool,
+    previous: Option<u8>,
+}
+
+impl<'a> GlyphIterator<'a> {
+    /// Lays the glyphs out in whole cells, without kerning.
+    pub fn monospace(mut self) -> GlyphIterator<'a> {
+        self.monospace = true;
+        self
+    }
 }
 
 impl<'a> Iterator for GlyphIterator<'a> {
@@ -126,7 +260,15 @@ impl<'a> Iterator for GlyphIterator<'a> {
         if self.chars.is_empty() {
             return None;
         }
-        let src_rect = self.atlas.glyph_rect(self.chars[0]);
+        let glyph = self.chars[0];
+        let src_rect = if self.monospace {
+            self.atlas.glyph_rect(glyph)
+        } else {
+            if let Some(previous) = self.previous {
+                self.position.0 += self.atlas.kerning(previous, glyph) * self.scale as i32;
+            }
+            self.atlas.span_rect(glyph)
+        };
         let mut dst_rect = src_rect.clone();
         let w = dst_rect.width() * self.scale;
         let h = dst_rect.height() * self.scale;
@@ -136,6 +278,31 @@ impl<'a> Iterator for GlyphIterator<'a> {
         dst_rect.set_y(self.position.1);
         self.position.0 += dst_rect.width() as i32 + self.scale as i32;
         self.chars = &self.chars[1..];
+        self.previous = Some(glyph);
         Some((src_rect, dst_rect))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proportional_and_monospace() {
+        let mut atlas = GlyphAtlas::new();
+        assert_eq!(atlas.text_width("", 1, Spacing::Proportional), 0);
+        assert_eq!(atlas.text_width("MATO", 2, Spacing::Monospace), (4 * 8 + 3) * 2);
+        let i = atlas.span_rect(b'i').width() as i32;
+        let m = atlas.span_rect(b'M').width() as i32;
+        assert!(i < m && m <= 8);
+        assert_eq!(atlas.text_width("iM", 1, Spacing::Proportional), i + 1 + m);
+        assert_eq!(atlas.span_rect(b' ').width(), 4);
+
+        let (a, v) = (atlas.span_rect(b'A').width() as i32, atlas.span_rect(b'V').width() as i32);
+        assert_eq!(atlas.text_width("AV", 1, Spacing::Proportional), a + v);
+        assert_eq!(atlas.text_width("AV", 1, Spacing::Monospace), 17);
+        atlas.add_kerning(b'A', b'V', 2);
+        let glyphs: Vec<_> = atlas.text_rects("AV", (10, 0), 2).collect();
+        assert_eq!(glyphs[1].1.x(), 10 + (a + 1 + 2) * 2);
+    }
+}
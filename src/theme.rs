@@ -0,0 +1,267 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use sdl2::pixels::Color;
+
+use constants::*;
+
+/// How a block of the arena is drawn. Themes meant for color blind players
+/// give apples and the worm different shapes, not just different colors.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shape {
+    Square,
+    Circle,
+    Diamond,
+    /// Tiles from the worm tile sheet. Anything else than the worm draws these as squares.
+    Sprites,
+}
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub wall: Color,
+    pub apple: Color,
+    pub golden_apple: Color,
+    pub poison_apple: Color,
+    pub slow_apple: Color,
+    pub worm: Color,
+    pub dead_worm: Color,
+    pub title_1: Color,
+    pub title_2: Color,
+    pub points: Color,
+    pub apple_shape: Shape,
+    pub worm_shape: Shape,
+    /// A font file in `THEME_DIR` to draw text with, see `font::load`.
+    pub font: Option<String>,
+}
+
+fn rgb(color: (u8, u8, u8)) -> Color {
+    Color::RGB(color.0, color.1, color.2)
+}
+
+impl Theme {
+    pub fn classic() -> Theme {
+        Theme {
+            name: "Classic".to_string(),
+            background: BACKGROUND_COLOR,
+            wall: WALL_COLOR,
+            apple: APPLE_COLOR,
+            golden_apple: Color::RGB(255, 215, 0),
+            poison_apple: Color::RGB(160, 0, 200),
+            slow_apple: Color::RGB(100, 200, 255),
+            worm: WORM_COLOR,
+            dead_worm: DEAD_WORM_COLOR,
+            title_1: rgb(TITLE_COLOR_1),
+            title_2: rgb(TITLE_COLOR_2),
+            points: rgb(POINTS_COLOR),
+            apple_shape: Shape::Square,
+            worm_shape: Shape::Sprites,
+            font: None,
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "High contrast".to_string(),
+            background: Color::RGB(0, 0, 0),
+            wall: Color::RGB(255, 255, 255),
+            apple: Color::RGB(255, 0, 255),
+            golden_apple: Color::RGB(255, 200, 0),
+            poison_apple: Color::RGB(0, 255, 0),
+            slow_apple: Color::RGB(0, 255, 255),
+            worm: Color::RGB(255, 255, 0),
+            dead_worm: Color::RGB(128, 128, 128),
+            title_1: Color::RGB(128, 128, 128),
+            title_2: Color::RGB(255, 255, 255),
+            points: Color::RGB(255, 255, 255),
+            apple_shape: Shape::Circle,
+            worm_shape: Shape::Sprites,
+            font: None,
+        }
+    }
+
+    /// Blue and orange from the Okabe-Ito palette, which stay apart for
+    /// red-green color blindness.
+    pub fn deuteranopia() -> Theme {
+        Theme {
+            name: "Deuteranopia".to_string(),
+            background: Color::RGB(0, 0, 0),
+            wall: Color::RGB(120, 120, 120),
+            apple: Color::RGB(230, 159, 0),
+            golden_apple: Color::RGB(240, 228, 66),
+            poison_apple: Color::RGB(204, 121, 167),
+            slow_apple: Color::RGB(0, 158, 115),
+            worm: Color::RGB(86, 180, 233),
+            dead_worm: Color::RGB(255, 255, 255),
+            title_1: Color::RGB(0, 114, 178),
+            title_2: Color::RGB(86, 180, 233),
+            points: Color::RGB(86, 180, 233),
+            apple_shape: Shape::Diamond,
+            worm_shape: Shape::Sprites,
+            font: None,
+        }
+    }
+
+    /// Like the deuteranopia theme, but with a brighter apple, as reds look
+    /// dark to protanopes.
+    pub fn protanopia() -> Theme {
+        Theme {
+            name: "Protanopia".to_string(),
+            apple: Color::RGB(240, 228, 66),
+            golden_apple: Color::RGB(230, 159, 0),
+            worm: Color::RGB(0, 114, 178),
+            title_1: Color::RGB(0, 114, 178),
+            title_2: Color::RGB(240, 228, 66),
+            points: Color::RGB(240, 228, 66),
+            ..Theme::deuteranopia()
+        }
+    }
+
+    pub fn presets() -> Vec<Theme> {
+        vec![Theme::classic(), Theme::high_contrast(), Theme::deuteranopia(), Theme::protanopia()]
+    }
+
+    /// Every color the theme uses, for things like GIF palettes.
+    pub fn palette(&self) -> Vec<Color> {
+        vec![self.background, self.wall, self.apple, self.golden_apple, self.poison_apple, self.slow_apple, self.worm, self.dead_worm, self.title_1, self.title_2, self.points]
+    }
+
+    /// Parses a theme file. Each line is `key = value`, where the value is a
+    /// `#rrggbb` color, a shape name or, for `font`, a file name; `#` at the start of a line begins a
+    /// comment. Keys that are left out keep the values of the classic theme.
+    pub fn parse(text: &str) -> Result<Theme, ThemeError> {
+        let mut theme = Theme::classic();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(ThemeError::Syntax(line_number)),
+            };
+            let color = || parse_color(value).ok_or_else(|| ThemeError::BadValue(line_number, value.to_string()));
+            let shape = || parse_shape(value).ok_or_else(|| ThemeError::BadValue(line_number, value.to_string()));
+            match key {
+                "name" => theme.name = value.to_string(),
+                "background" => theme.background = color()?,
+                "wall" => theme.wall = color()?,
+                "apple" => theme.apple = color()?,
+                "golden_apple" => theme.golden_apple = color()?,
+                "poison_apple" => theme.poison_apple = color()?,
+                "slow_apple" => theme.slow_apple = color()?,
+                "worm" => theme.worm = color()?,
+                "dead_worm" => theme.dead_worm = color()?,
+                "title_1" => theme.title_1 = color()?,
+                "title_2" => theme.title_2 = color()?,
+                "points" => theme.points = color()?,
+                "apple_shape" => theme.apple_shape = shape()?,
+                "worm_shape" => theme.worm_shape = shape()?,
+                "font" => theme.font = Some(value.to_string()),
+                _ => return Err(ThemeError::UnknownKey(line_number, key.to_string())),
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn load(path: &Path) -> Result<Theme, ThemeError> {
+        let mut text = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut text)).map_err(|e| ThemeError::Io(e.to_string()))?;
+        Theme::parse(&text)
+    }
+}
+
+/// The preset themes followed by the `*.theme` files in `THEME_DIR`.
+/// Files that can't be loaded are reported and skipped.
+pub fn all_themes() -> Vec<Theme> {
+    let mut themes = Theme::presets();
+    let entries = match fs::read_dir(THEME_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return themes,
+    };
+    let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "theme"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match Theme::load(&path) {
+            Ok(theme) => themes.push(theme),
+            Err(e) => eprintln!("Could not load theme {}: {}", path.display(), e),
+        }
+    }
+    themes
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    if value.len() != 7 || !value.is_ascii() || !value.starts_with('#') {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&value[i..i + 2], 16).ok();
+    Some(Color::RGB(component(1)?, component(3)?, component(5)?))
+}
+
+fn parse_shape(value: &str) -> Option<Shape> {
+    match value {
+        "square" => Some(Shape::Square),
+        "circle" => Some(Shape::Circle),
+        "diamond" => Some(Shape::Diamond),
+        "sprites" => Some(Shape::Sprites),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(String),
+    Syntax(usize),
+    UnknownKey(usize, String),
+    BadValue(usize, String),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ThemeError::Io(ref e) => write!(f, "{}", e),
+            ThemeError::Syntax(line) => write!(f, "line {}: expected key = value", line),
+            ThemeError::UnknownKey(line, ref key) => write!(f, "line {}: unknown key {}", line, key),
+            ThemeError::BadValue(line, ref value) => write!(f, "line {}: bad value {}", line, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_theme_file() {
+        let theme = Theme::parse("# Comment\nname = Mine\napple = #ff8000\napple_shape = diamond\nfont = mine.psf\n").unwrap();
+        assert_eq!(theme.name, "Mine");
+        assert_eq!(theme.apple, Color::RGB(255, 128, 0));
+        assert_eq!(theme.apple_shape, Shape::Diamond);
+        assert_eq!(theme.worm, WORM_COLOR);
+        assert_eq!(theme.font, Some("mine.psf".to_string()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        match Theme::parse("apple = red") {
+            Err(ThemeError::BadValue(1, _)) => {},
+            other => panic!("Unexpected {:?}", other),
+        }
+        match Theme::parse("apple = #aébcd") {
+            Err(ThemeError::BadValue(1, _)) => {},
+            other => panic!("Unexpected {:?}", other),
+        }
+        match Theme::parse("\nsnake = #000000") {
+            Err(ThemeError::UnknownKey(2, _)) => {},
+            other => panic!("Unexpected {:?}", other),
+        }
+    }
+}